@@ -0,0 +1,319 @@
+//! This module contains parsers for archives that are passed as modules, e.g. an initrd.
+//!
+//! Supported are cpio archives in the "newc" and "crc" formats (as created by
+//! `cpio -H newc`) and POSIX ustar archives. Nothing is copied, the entries refer
+//! to the bytes of the module:
+//!
+//! ```rust,no_run
+//! # use multiboot::information::Multiboot;
+//! # fn f(multiboot: &Multiboot) {
+//! use multiboot::archive::Archive;
+//!
+//! let initrd = multiboot.module_by_name("initrd").unwrap();
+//! let archive = Archive::new(multiboot.module_data(&initrd).unwrap()).unwrap();
+//! let init = archive.get("/sbin/init").unwrap();
+//! # }
+//! ```
+//!
+//! Iteration stops at the end marker of the archive or at the first malformed entry.
+
+use core::str;
+
+/// Mask for the file type bits of [`Entry::mode`].
+///
+/// [`Entry::mode`]: struct.Entry.html#structfield.mode
+pub const S_IFMT: u32 = 0o170000;
+/// File type of directories.
+pub const S_IFDIR: u32 = 0o040000;
+/// File type of regular files.
+pub const S_IFREG: u32 = 0o100000;
+/// File type of symbolic links.
+pub const S_IFLNK: u32 = 0o120000;
+
+/// Supported archive formats.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// cpio, "newc" or "crc" format.
+    Cpio,
+    /// POSIX ustar (or GNU tar).
+    Tar,
+}
+
+/// A file, directory or link in an archive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Entry<'a> {
+    /// Directory part of the path, only used by ustar for long paths.
+    pub prefix: &'a str,
+    /// Name of the entry, as stored in the archive.
+    pub name: &'a str,
+    /// File type and permissions, see [`S_IFMT`](constant.S_IFMT.html).
+    pub mode: u32,
+    /// Contents of the entry, the target for symbolic links.
+    pub data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    /// Get the size of the contents.
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Is this a regular file?
+    pub fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    /// Is this a directory?
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    /// Is this a symbolic link?
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    /// Check whether this entry is located at `path`.
+    ///
+    /// Leading `/` and `./` as well as trailing `/` are ignored on both sides,
+    /// so `/bin/sh`, `bin/sh` and `./bin/sh` are all the same.
+    pub fn has_path(&self, path: &str) -> bool {
+        let path = normalize(path);
+        let prefix = normalize(self.prefix);
+        let name = normalize(self.name);
+        if prefix.is_empty() {
+            return path == name;
+        }
+        path.len() == prefix.len() + 1 + name.len()
+            && path.starts_with(prefix)
+            && path[prefix.len()..].starts_with('/')
+            && path.ends_with(name)
+    }
+}
+
+/// Remove leading `/` and `./` and trailing `/`.
+fn normalize(mut path: &str) -> &str {
+    loop {
+        let trimmed = path.trim_start_matches('/');
+        let trimmed = trimmed.strip_prefix("./").unwrap_or(trimmed);
+        if trimmed.len() == path.len() {
+            break;
+        }
+        path = trimmed;
+    }
+    match path {
+        "." => "",
+        path => path.trim_end_matches('/'),
+    }
+}
+
+/// An archive of any supported format.
+#[derive(Debug, Copy, Clone)]
+pub struct Archive<'a> {
+    data: &'a [u8],
+    format: Format,
+}
+
+impl<'a> Archive<'a> {
+    /// Detect the format of `data`.
+    ///
+    /// Returns `None` if it's neither cpio nor ustar.
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let format = if data.starts_with(CPIO_NEWC_MAGIC) || data.starts_with(CPIO_CRC_MAGIC) {
+            Format::Cpio
+        } else if data.get(257..262) == Some(TAR_MAGIC) {
+            Format::Tar
+        } else {
+            return None;
+        };
+        Some(Self { data, format })
+    }
+
+    /// Get the format of the archive.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Iterate over all entries.
+    pub fn entries(&self) -> Entries<'a> {
+        match self.format {
+            Format::Cpio => Entries::Cpio(CpioIter::new(self.data)),
+            Format::Tar => Entries::Tar(TarIter::new(self.data)),
+        }
+    }
+
+    /// Find the entry at `path`.
+    ///
+    /// See [`Entry::has_path`] for how paths are compared.
+    ///
+    /// [`Entry::has_path`]: struct.Entry.html#method.has_path
+    pub fn get(&self, path: &str) -> Option<Entry<'a>> {
+        self.entries().find(|entry| entry.has_path(path))
+    }
+}
+
+/// Used to iterate over the entries of an [`Archive`].
+///
+/// [`Archive`]: struct.Archive.html
+#[derive(Debug, Clone)]
+pub enum Entries<'a> {
+    Cpio(CpioIter<'a>),
+    Tar(TarIter<'a>),
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        match self {
+            Entries::Cpio(iter) => iter.next(),
+            Entries::Tar(iter) => iter.next(),
+        }
+    }
+}
+
+const CPIO_NEWC_MAGIC: &[u8] = b"070701";
+const CPIO_CRC_MAGIC: &[u8] = b"070702";
+const CPIO_HEADER_SIZE: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+/// Used to iterate over a cpio archive in the "newc" or "crc" format.
+#[derive(Debug, Clone)]
+pub struct CpioIter<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> CpioIter<'a> {
+    /// Iterate over the entries in `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { rest: data }
+    }
+
+    fn parse(&self) -> Option<(Entry<'a>, usize)> {
+        let header = self.rest.get(..CPIO_HEADER_SIZE)?;
+        if !header.starts_with(CPIO_NEWC_MAGIC) && !header.starts_with(CPIO_CRC_MAGIC) {
+            return None;
+        }
+        // all fields are 8 hexadecimal digits after the magic
+        let field = |index: usize| {
+            let start = 6 + index * 8;
+            str::from_utf8(&header[start..start + 8])
+                .ok()
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+        };
+        let mode = field(1)?;
+        let file_size = field(6)? as usize;
+        let name_size = field(11)? as usize;
+        // the name includes the terminating NUL
+        let name_end = CPIO_HEADER_SIZE.checked_add(name_size)?;
+        let name = self.rest.get(CPIO_HEADER_SIZE..name_end.checked_sub(1)?)?;
+        let name = str::from_utf8(name).ok()?;
+        let data_start = round_up!(name_end, 4);
+        let data_end = data_start.checked_add(file_size)?;
+        let data = self.rest.get(data_start..data_end)?;
+        let entry = Entry {
+            prefix: "",
+            name,
+            mode,
+            data,
+        };
+        Some((entry, round_up!(data_end, 4)))
+    }
+}
+
+impl<'a> Iterator for CpioIter<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        match self.parse() {
+            Some((entry, _)) if entry.name == CPIO_TRAILER => None,
+            Some((entry, size)) => {
+                self.rest = self.rest.get(size..).unwrap_or(&[]);
+                Some(entry)
+            }
+            None => None,
+        }
+    }
+}
+
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// Used to iterate over a POSIX ustar archive.
+#[derive(Debug, Clone)]
+pub struct TarIter<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> TarIter<'a> {
+    /// Iterate over the entries in `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { rest: data }
+    }
+
+    fn parse(&self) -> Option<(Entry<'a>, usize)> {
+        let header = self.rest.get(..TAR_BLOCK_SIZE)?;
+        // the archive ends with zero blocks
+        if header.iter().all(|&b| b == 0) || &header[257..262] != TAR_MAGIC {
+            return None;
+        }
+        let checksum: u32 = header
+            .iter()
+            .enumerate()
+            .map(|(index, &b)| match index {
+                148..=155 => u32::from(b' '),
+                _ => u32::from(b),
+            })
+            .sum();
+        if tar_number(&header[148..156])? != checksum {
+            return None;
+        }
+        let file_type = match header[156] {
+            b'0' | b'\0' | b'7' => S_IFREG,
+            b'2' => S_IFLNK,
+            b'5' => S_IFDIR,
+            _ => 0,
+        };
+        let mode = tar_number(&header[100..108])? & !S_IFMT | file_type;
+        let size = tar_number(&header[124..136])? as usize;
+        let data = match file_type {
+            // the target isn't stored in the data
+            S_IFLNK => tar_string(&header[157..257])?.as_bytes(),
+            _ => self
+                .rest
+                .get(TAR_BLOCK_SIZE..TAR_BLOCK_SIZE.checked_add(size)?)?,
+        };
+        let entry = Entry {
+            prefix: tar_string(&header[345..500])?,
+            name: tar_string(&header[0..100])?,
+            mode,
+            data,
+        };
+        Some((entry, TAR_BLOCK_SIZE + round_up!(size, TAR_BLOCK_SIZE)))
+    }
+}
+
+impl<'a> Iterator for TarIter<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Entry<'a>> {
+        let (entry, size) = self.parse()?;
+        self.rest = self.rest.get(size..).unwrap_or(&[]);
+        Some(entry)
+    }
+}
+
+/// Parse a NUL-terminated (or NUL-padded) string field.
+fn tar_string(field: &[u8]) -> Option<&str> {
+    let length = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    str::from_utf8(&field[..length]).ok()
+}
+
+/// Parse an octal number field, which may be padded with spaces and NULs.
+fn tar_number(field: &[u8]) -> Option<u32> {
+    let digits = tar_string(field)?.trim_matches(' ');
+    match digits {
+        "" => Some(0),
+        digits => u32::from_str_radix(digits, 8).ok(),
+    }
+}
@@ -0,0 +1,203 @@
+//! This module lets a kernel declare boot parameters next to the code that uses them.
+//!
+//! Parameters are registered with [`boot_param!`], which places a [`BootParam`]
+//! into the `multiboot_params` link section. [`dispatch`] then walks the command
+//! line once and calls the handler of every parameter it finds:
+//!
+//! ```rust
+//! #[macro_use]
+//! extern crate multiboot;
+//!
+//! use std::sync::atomic::{AtomicU32, Ordering};
+//! use multiboot::boot_params::{self, ParamError};
+//! use multiboot::cmdline::{CommandLine, Word};
+//!
+//! static LOGLEVEL: AtomicU32 = AtomicU32::new(4);
+//!
+//! fn set_loglevel(value: Option<Word>) -> bool {
+//!     match value.and_then(|value| value.parse()) {
+//!         Some(level) => {
+//!             LOGLEVEL.store(level, Ordering::Relaxed);
+//!             true
+//!         }
+//!         None => false,
+//!     }
+//! }
+//!
+//! boot_param!("loglevel", set_loglevel);
+//!
+//! fn main() {
+//!     // this would be `multiboot.command_line()`
+//!     let cmdline = CommandLine::new("/boot/kernel loglevel=7 foo");
+//!     let mut unknown = None;
+//!     boot_params::dispatch(cmdline, boot_params::registered(), |error| {
+//!         if let ParamError::Unknown(param) = error {
+//!             unknown = Some(param.key.raw());
+//!         }
+//!     });
+//!     assert_eq!(LOGLEVEL.load(Ordering::Relaxed), 7);
+//!     assert_eq!(unknown, Some("foo"));
+//! }
+//! ```
+//!
+//! This module is only available with the `boot_params` feature.
+//! The registry relies on the linker defining `__start_multiboot_params` and
+//! `__stop_multiboot_params`, which GNU ld and lld do for ELF targets.
+//! A custom linker script has to keep the section and define both symbols.
+//! The section always contains an entry of this crate, so it exists even if
+//! no parameter is declared.
+//!
+//! [`boot_param!`]: ../macro.boot_param.html
+//! [`BootParam`]: struct.BootParam.html
+//! [`dispatch`]: fn.dispatch.html
+
+use core::mem::size_of;
+use core::ptr;
+use core::slice;
+
+use cmdline::{CommandLine, Param, Word};
+
+/// Called with the value of a parameter, `None` for bare flags.
+///
+/// Returns `false` if the value is invalid.
+pub type Handler = fn(Option<Word>) -> bool;
+
+/// A boot parameter and the function that handles it.
+#[derive(Copy, Clone)]
+pub struct BootParam {
+    /// Name of the parameter, i.e. the part before `=`.
+    pub name: &'static str,
+    /// Handler for the value.
+    pub handler: Handler,
+    /// Whether the parameter is handled by [`dispatch_early`].
+    ///
+    /// [`dispatch_early`]: fn.dispatch_early.html
+    pub early: bool,
+}
+
+/// Declare a boot parameter.
+///
+/// The handler is a `fn(Option<Word>) -> bool`, see [`Handler`].
+/// Prefix the name with `early` to handle the parameter in [`dispatch_early`].
+///
+/// ```rust,ignore
+/// boot_param!("loglevel", set_loglevel);
+/// boot_param!(early "earlycon", setup_earlycon);
+/// ```
+///
+/// [`Handler`]: boot_params/type.Handler.html
+/// [`dispatch_early`]: boot_params/fn.dispatch_early.html
+#[macro_export]
+macro_rules! boot_param {
+    (early $name:expr, $handler:expr) => {
+        $crate::boot_param!(@register $name, $handler, true);
+    };
+    ($name:expr, $handler:expr) => {
+        $crate::boot_param!(@register $name, $handler, false);
+    };
+    (@register $name:expr, $handler:expr, $early:expr) => {
+        const _: () = {
+            #[used]
+            #[link_section = "multiboot_params"]
+            static PARAM: $crate::boot_params::BootParam = $crate::boot_params::BootParam {
+                name: $name,
+                handler: $handler,
+                early: $early,
+            };
+        };
+    };
+}
+
+// only the addresses of these are used
+extern "C" {
+    static __start_multiboot_params: u8;
+    static __stop_multiboot_params: u8;
+}
+
+/// Keeps the section from being empty, this isn't a parameter.
+#[used]
+#[link_section = "multiboot_params"]
+static SENTINEL: BootParam = BootParam {
+    name: "",
+    handler: ignore,
+    early: false,
+};
+
+fn ignore(_value: Option<Word>) -> bool {
+    true
+}
+
+/// Get all parameters declared using [`boot_param!`].
+///
+/// [`boot_param!`]: ../macro.boot_param.html
+pub fn registered() -> impl Iterator<Item = &'static BootParam> + Clone {
+    let params = unsafe {
+        let start = ptr::addr_of!(__start_multiboot_params);
+        let stop = ptr::addr_of!(__stop_multiboot_params);
+        let count = (stop as usize - start as usize) / size_of::<BootParam>();
+        slice::from_raw_parts(start.cast::<BootParam>(), count)
+    };
+    params.iter().filter(|param| !ptr::eq(*param, &SENTINEL))
+}
+
+/// Problems found while dispatching the parameters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParamError<'a> {
+    /// No handler is declared for this parameter.
+    Unknown(Param<'a>),
+    /// The handler rejected the value.
+    Invalid(Param<'a>),
+}
+
+/// Call the handlers of all parameters on the command line that aren't early.
+///
+/// Parameters without any handler are passed to `report` as [`ParamError::Unknown`],
+/// rejected values as [`ParamError::Invalid`].
+/// If a parameter is given multiple times, its handler is called for each of them.
+///
+/// [`ParamError::Unknown`]: enum.ParamError.html#variant.Unknown
+/// [`ParamError::Invalid`]: enum.ParamError.html#variant.Invalid
+pub fn dispatch<'a, 'p, P, F>(cmdline: CommandLine<'a>, params: P, report: F)
+where
+    P: IntoIterator<Item = &'p BootParam>,
+    P::IntoIter: Clone,
+    F: FnMut(ParamError<'a>),
+{
+    dispatch_stage(cmdline, params, false, report)
+}
+
+/// Call the handlers of all early parameters on the command line.
+///
+/// This is meant to be called before [`dispatch`], e.g. before there is a console.
+/// Unknown parameters are not reported here, since [`dispatch`] does that.
+///
+/// [`dispatch`]: fn.dispatch.html
+pub fn dispatch_early<'a, 'p, P, F>(cmdline: CommandLine<'a>, params: P, report: F)
+where
+    P: IntoIterator<Item = &'p BootParam>,
+    P::IntoIter: Clone,
+    F: FnMut(ParamError<'a>),
+{
+    dispatch_stage(cmdline, params, true, report)
+}
+
+fn dispatch_stage<'a, 'p, P, F>(cmdline: CommandLine<'a>, params: P, early: bool, mut report: F)
+where
+    P: IntoIterator<Item = &'p BootParam>,
+    P::IntoIter: Clone,
+    F: FnMut(ParamError<'a>),
+{
+    let params = params.into_iter();
+    for param in cmdline.params() {
+        let mut known = false;
+        for boot_param in params.clone().filter(|p| param.key == *p.name) {
+            known = true;
+            if boot_param.early == early && !(boot_param.handler)(param.value) {
+                report(ParamError::Invalid(param));
+            }
+        }
+        if !known && !early {
+            report(ParamError::Unknown(param));
+        }
+    }
+}
@@ -0,0 +1,357 @@
+//! This module contains a parser for kernel and module command lines.
+//!
+//! GRUB passes the path of the kernel (or module) as the first word,
+//! followed by the arguments given in its configuration:
+//!
+//! ```text
+//! /boot/kernel console=ttyS0 loglevel=7 quiet msg="hello world" -- single
+//! ```
+//!
+//! Words are separated by whitespace. Double and single quotes group words
+//! and a backslash escapes the next character (except within single quotes).
+//! Each word after the path is a parameter, either `key=value` or a bare flag.
+//! Everything after `--` is passed on to init.
+//!
+//! Nothing is allocated: Parameters refer to the original string and are
+//! unescaped on demand.
+//!
+//! ```rust
+//! use multiboot::cmdline::CommandLine;
+//!
+//! let cmdline = CommandLine::new("/boot/kernel loglevel=7 quiet -- single");
+//! assert_eq!(cmdline.path().unwrap(), "/boot/kernel");
+//! assert_eq!(cmdline.get::<u32>("loglevel"), Some(7));
+//! assert!(cmdline.flag("quiet"));
+//! assert_eq!(cmdline.init_args().next().unwrap(), "single");
+//! ```
+
+use core::fmt;
+use core::str::{self, FromStr};
+
+/// Maximum length of an escaped value that [`Word::parse`] can handle.
+///
+/// [`Word::parse`]: struct.Word.html#method.parse
+pub const MAX_PARSE_LENGTH: usize = 64;
+
+/// Separates the parameters from the arguments for init.
+const SEPARATOR: &str = "--";
+
+/// A command line of a kernel or a module.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CommandLine<'a> {
+    raw: &'a str,
+}
+
+impl<'a> CommandLine<'a> {
+    /// Parse the given command line lazily.
+    ///
+    /// The first word is considered to be the path.
+    pub fn new(raw: &'a str) -> Self {
+        Self { raw }
+    }
+
+    /// Get the unparsed command line.
+    pub fn as_str(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Iterate over all words, including the path and the separator.
+    pub fn words(&self) -> Words<'a> {
+        Words { rest: self.raw }
+    }
+
+    /// Get the path of the kernel or module.
+    pub fn path(&self) -> Option<Word<'a>> {
+        self.words().next()
+    }
+
+    /// Get everything after the path, e.g. to pass it on unchanged.
+    pub fn arguments(&self) -> &'a str {
+        let mut words = self.words();
+        words.next();
+        words.rest.trim_start_matches(is_whitespace)
+    }
+
+    /// Iterate over all parameters between the path and `--`.
+    pub fn params(&self) -> Params<'a> {
+        let mut words = self.words();
+        words.next();
+        Params { words }
+    }
+
+    /// Iterate over the arguments after `--`.
+    pub fn init_args(&self) -> Words<'a> {
+        let mut words = self.words();
+        words.next();
+        for word in words.by_ref() {
+            if word.raw == SEPARATOR {
+                return words;
+            }
+        }
+        words
+    }
+
+    /// Get the value of the last parameter named `key`.
+    ///
+    /// Returns `None` if there is no such parameter or it's a bare flag.
+    pub fn value(&self, key: &str) -> Option<Word<'a>> {
+        self.params()
+            .filter(|param| param.key == *key)
+            .last()
+            .and_then(|param| param.value)
+    }
+
+    /// Iterate over the values of all parameters named `key`.
+    ///
+    /// Bare flags are skipped.
+    pub fn values<'k>(&self, key: &'k str) -> impl Iterator<Item = Word<'a>> + 'k
+    where
+        'a: 'k,
+    {
+        self.params()
+            .filter(move |param| param.key == *key)
+            .filter_map(|param| param.value)
+    }
+
+    /// Parse the value of the last parameter named `key`.
+    ///
+    /// Returns `None` if there is no such parameter or its value doesn't parse.
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.value(key).and_then(|value| value.parse())
+    }
+
+    /// Check whether there is a parameter named `key`.
+    pub fn contains(&self, key: &str) -> bool {
+        self.params().any(|param| param.key == *key)
+    }
+
+    /// Check whether the boolean flag `key` is set.
+    ///
+    /// A bare `key` sets the flag, so do the values `1`, `y`, `yes`, `on` and `true`.
+    /// The last parameter wins.
+    pub fn flag(&self, key: &str) -> bool {
+        match self.params().filter(|param| param.key == *key).last() {
+            Some(Param { value: None, .. }) => true,
+            Some(Param {
+                value: Some(value), ..
+            }) => ["1", "y", "yes", "on", "true"]
+                .iter()
+                .any(|truthy| value == *truthy),
+            None => false,
+        }
+    }
+}
+
+impl<'a> fmt::Display for CommandLine<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.raw)
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r'
+}
+
+/// Find the end of the first word and the first unquoted `=` in it.
+fn scan(raw: &str) -> (usize, Option<usize>) {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut equals = None;
+    for (index, c) in raw.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some('"'), '"') => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '=') if equals.is_none() => equals = Some(index),
+            (None, c) if is_whitespace(c) => return (index, equals),
+            _ => {}
+        }
+    }
+    (raw.len(), equals)
+}
+
+/// A single word of a command line, possibly containing quotes and escapes.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Word<'a> {
+    raw: &'a str,
+}
+
+impl<'a> Word<'a> {
+    /// Get the word as it appears on the command line.
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    /// Get the word without allocating if it doesn't need to be unescaped.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.raw.contains(['"', '\'', '\\']) {
+            true => None,
+            false => Some(self.raw),
+        }
+    }
+
+    /// Iterate over the characters with quotes and escapes removed.
+    pub fn chars(&self) -> Unescape<'a> {
+        Unescape {
+            chars: self.raw.chars(),
+            quote: None,
+        }
+    }
+
+    /// Write the unescaped word to `buffer`.
+    ///
+    /// Returns `None` if it doesn't fit.
+    pub fn unescape_into<'b>(&self, buffer: &'b mut [u8]) -> Option<&'b str> {
+        let mut length = 0;
+        for c in self.chars() {
+            let dst = buffer.get_mut(length..length + c.len_utf8())?;
+            c.encode_utf8(dst);
+            length += c.len_utf8();
+        }
+        // we only wrote whole characters
+        str::from_utf8(&buffer[..length]).ok()
+    }
+
+    /// Parse the unescaped word.
+    ///
+    /// Words that need to be unescaped may be at most [`MAX_PARSE_LENGTH`] bytes long.
+    ///
+    /// [`MAX_PARSE_LENGTH`]: constant.MAX_PARSE_LENGTH.html
+    pub fn parse<T: FromStr>(&self) -> Option<T> {
+        match self.as_str() {
+            Some(s) => s.parse().ok(),
+            None => {
+                let mut buffer = [0; MAX_PARSE_LENGTH];
+                self.unescape_into(&mut buffer)?.parse().ok()
+            }
+        }
+    }
+}
+
+impl<'a> PartialEq<str> for Word<'a> {
+    fn eq(&self, other: &str) -> bool {
+        self.chars().eq(other.chars())
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for Word<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        *self == **other
+    }
+}
+
+impl<'a> fmt::Debug for Word<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.raw, f)
+    }
+}
+
+impl<'a> fmt::Display for Word<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use core::fmt::Write;
+        self.chars().try_for_each(|c| f.write_char(c))
+    }
+}
+
+/// Used to iterate over the unescaped characters of a [`Word`].
+///
+/// [`Word`]: struct.Word.html
+#[derive(Clone)]
+pub struct Unescape<'a> {
+    chars: str::Chars<'a>,
+    quote: Option<char>,
+}
+
+impl<'a> Iterator for Unescape<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            let c = self.chars.next()?;
+            match (self.quote, c) {
+                (Some('\''), '\'') => self.quote = None,
+                (Some('\''), _) => return Some(c),
+                // a trailing backslash is kept
+                (_, '\\') => return Some(self.chars.next().unwrap_or(c)),
+                (Some('"'), '"') => self.quote = None,
+                (None, '"') | (None, '\'') => self.quote = Some(c),
+                _ => return Some(c),
+            }
+        }
+    }
+}
+
+/// Used to iterate over the words of a command line.
+#[derive(Debug, Clone)]
+pub struct Words<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Words<'a> {
+    type Item = Word<'a>;
+
+    fn next(&mut self) -> Option<Word<'a>> {
+        let rest = self.rest.trim_start_matches(is_whitespace);
+        if rest.is_empty() {
+            self.rest = rest;
+            return None;
+        }
+        let (end, _) = scan(rest);
+        let (raw, rest) = rest.split_at(end);
+        self.rest = rest;
+        Some(Word { raw })
+    }
+}
+
+/// A parameter on the command line, either `key=value` or a bare flag.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Param<'a> {
+    /// The name of the parameter.
+    pub key: Word<'a>,
+    /// The value, `None` for bare flags.
+    pub value: Option<Word<'a>>,
+}
+
+impl<'a> Param<'a> {
+    /// Split a word at the first unquoted `=`.
+    pub fn from_word(word: Word<'a>) -> Self {
+        match scan(word.raw) {
+            (_, Some(equals)) => Param {
+                key: Word {
+                    raw: &word.raw[..equals],
+                },
+                value: Some(Word {
+                    raw: &word.raw[equals + 1..],
+                }),
+            },
+            (_, None) => Param {
+                key: word,
+                value: None,
+            },
+        }
+    }
+}
+
+/// Used to iterate over the parameters of a command line.
+#[derive(Debug, Clone)]
+pub struct Params<'a> {
+    words: Words<'a>,
+}
+
+impl<'a> Iterator for Params<'a> {
+    type Item = Param<'a>;
+
+    fn next(&mut self) -> Option<Param<'a>> {
+        let word = self.words.next()?;
+        if word.raw == SEPARATOR {
+            // the rest belongs to init
+            self.words.rest = "";
+            return None;
+        }
+        Some(Param::from_word(word))
+    }
+}
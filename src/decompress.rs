@@ -0,0 +1,231 @@
+//! This module inflates gzip and zlib compressed modules.
+//!
+//! It is only available with the `decompress` feature.
+//!
+//! The decompressed copy is placed into memory obtained from
+//! [`MemoryAllocator::allocate`], use [`Multiboot::decompress_module`]
+//! to also update the module table.
+//!
+//! [`MemoryAllocator::allocate`]: ../information/trait.MemoryAllocator.html#tymethod.allocate
+//! [`Multiboot::decompress_module`]: ../information/struct.Multiboot.html#method.decompress_module
+
+use core::convert::TryInto;
+
+use miniz_oxide::inflate::core::inflate_flags::{
+    TINFL_FLAG_PARSE_ZLIB_HEADER, TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+};
+use miniz_oxide::inflate::core::{decompress, DecompressorOxide, TINFL_LZ_DICT_SIZE};
+use miniz_oxide::inflate::TINFLStatus;
+
+use information::{AllocationPurpose, AllocationRequest, MemoryManagement, Module, PAddr};
+
+/// Supported compression formats.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compression {
+    /// gzip (RFC 1952), only the first member is decompressed.
+    Gzip,
+    /// zlib (RFC 1950).
+    Zlib,
+}
+
+impl Compression {
+    /// Detect the format of `data` from its header.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x1f, 0x8b, 8, ..] => Some(Compression::Gzip),
+            // deflate with a valid header checksum
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                Some(Compression::Zlib)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reasons why a module can't be decompressed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecompressError {
+    /// The data is neither gzip nor zlib.
+    UnknownFormat,
+    /// The compressed data is malformed or truncated.
+    Corrupt,
+    /// The checksum of the decompressed data doesn't match.
+    ChecksumMismatch,
+    /// The output buffer is too small.
+    BufferTooSmall,
+    /// The module can't be accessed.
+    Inaccessible,
+    /// Memory for the output couldn't be allocated.
+    AllocationFailed,
+}
+
+/// Get the size of the decompressed data.
+///
+/// For gzip this is read from the trailer (which only stores it modulo 4 GiB),
+/// zlib data has to be inflated once to find out.
+pub fn decompressed_size(data: &[u8]) -> Result<usize, DecompressError> {
+    match Compression::detect(data).ok_or(DecompressError::UnknownFormat)? {
+        Compression::Gzip => {
+            let trailer = data.len().checked_sub(4).ok_or(DecompressError::Corrupt)?;
+            let size = u32::from_le_bytes(data[trailer..].try_into().unwrap());
+            Ok(size as usize)
+        }
+        Compression::Zlib => {
+            // inflate into a circular buffer just to count the bytes
+            let mut decompressor = DecompressorOxide::new();
+            let mut window = [0; TINFL_LZ_DICT_SIZE];
+            let (mut input, mut size) = (data, 0);
+            loop {
+                let (status, read, written) = decompress(
+                    &mut decompressor,
+                    input,
+                    &mut window,
+                    size % TINFL_LZ_DICT_SIZE,
+                    TINFL_FLAG_PARSE_ZLIB_HEADER,
+                );
+                input = &input[read..];
+                size += written;
+                match status {
+                    TINFLStatus::Done => return Ok(size),
+                    TINFLStatus::HasMoreOutput => {}
+                    status => return Err(error(status)),
+                }
+            }
+        }
+    }
+}
+
+/// Decompress `data` into `out`.
+///
+/// Returns the number of bytes written.
+pub fn decompress_into(data: &[u8], out: &mut [u8]) -> Result<usize, DecompressError> {
+    match Compression::detect(data).ok_or(DecompressError::UnknownFormat)? {
+        Compression::Gzip => {
+            let (deflate, crc, size) = parse_gzip(data)?;
+            let written = inflate(deflate, out, 0)?;
+            if written as u32 != size || crc32(&out[..written]) != crc {
+                return Err(DecompressError::ChecksumMismatch);
+            }
+            Ok(written)
+        }
+        Compression::Zlib => inflate(data, out, TINFL_FLAG_PARSE_ZLIB_HEADER),
+    }
+}
+
+/// Decompress a module into newly allocated memory.
+///
+/// The returned module describes the decompressed copy and has the same string.
+/// The compressed data is left alone.
+///
+/// # Safety
+/// Same as [`Module::data`], `module` has to describe the memory of an actual module.
+///
+/// [`Module::data`]: ../information/struct.Module.html#method.data
+pub unsafe fn decompress_module<'a, M: MemoryManagement + ?Sized>(
+    module: &Module<'a>,
+    memory_management: &mut M,
+) -> Result<Module<'a>, DecompressError> {
+    let data = module
+        .data(memory_management)
+        .ok_or(DecompressError::Inaccessible)?;
+    let size = decompressed_size(data)?;
+    let request = AllocationRequest::new(size, AllocationPurpose::Module);
+    let (addr, out) = memory_management
+        .allocate_request(request)
+        .ok_or(DecompressError::AllocationFailed)?;
+    match decompress_into(data, &mut out[..size]) {
+        Ok(written) => Ok(Module::from_bytes(
+            addr,
+            addr + written as PAddr,
+            module.string_bytes(),
+        )),
+        Err(e) => {
+            memory_management.deallocate_sized(addr, size);
+            Err(e)
+        }
+    }
+}
+
+fn inflate(data: &[u8], out: &mut [u8], flags: u32) -> Result<usize, DecompressError> {
+    let mut decompressor = DecompressorOxide::new();
+    let (status, _, written) = decompress(
+        &mut decompressor,
+        data,
+        out,
+        0,
+        flags | TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+    );
+    match status {
+        TINFLStatus::Done => Ok(written),
+        status => Err(error(status)),
+    }
+}
+
+fn error(status: TINFLStatus) -> DecompressError {
+    match status {
+        TINFLStatus::HasMoreOutput => DecompressError::BufferTooSmall,
+        TINFLStatus::Adler32Mismatch => DecompressError::ChecksumMismatch,
+        _ => DecompressError::Corrupt,
+    }
+}
+
+const FHCRC: u8 = 1 << 1;
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+const FCOMMENT: u8 = 1 << 4;
+
+/// Split a gzip member into the deflate stream, CRC-32 and size.
+fn parse_gzip(data: &[u8]) -> Result<(&[u8], u32, u32), DecompressError> {
+    let corrupt = DecompressError::Corrupt;
+    let flags = *data.get(3).ok_or(corrupt)?;
+    let mut offset = 10;
+    if flags & FEXTRA != 0 {
+        let length = data.get(offset..offset + 2).ok_or(corrupt)?;
+        offset += 2 + usize::from(u16::from_le_bytes(length.try_into().unwrap()));
+    }
+    for &flag in [FNAME, FCOMMENT].iter() {
+        if flags & flag != 0 {
+            let rest = data.get(offset..).ok_or(corrupt)?;
+            offset += rest.iter().position(|&b| b == 0).ok_or(corrupt)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        offset += 2;
+    }
+    let trailer = data.len().checked_sub(8).ok_or(corrupt)?;
+    let deflate = data.get(offset..trailer).ok_or(corrupt)?;
+    let crc = u32::from_le_bytes(data[trailer..trailer + 4].try_into().unwrap());
+    let size = u32::from_le_bytes(data[trailer + 4..].try_into().unwrap());
+    Ok((deflate, crc, size))
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+/// Compute the CRC-32 used by gzip.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &b| {
+        CRC32_TABLE[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
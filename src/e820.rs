@@ -0,0 +1,222 @@
+//! This module converts between BIOS E820 memory maps and Multiboot memory maps.
+//!
+//! An E820 map is an array of entries that all have the same size:
+//! Either 20 bytes (base, length and type) or 24 bytes (ACPI 3.0 extended attributes).
+//! The Multiboot memory map contains the same data, but each entry is prefixed with its size.
+//!
+//! Converting from one to the other is lossless:
+//!
+//! ```rust
+//! use multiboot::e820::{E820Entry, E820Iter};
+//! # let bytes = [0u8; 40];
+//! // read a map from the BIOS
+//! let entries = E820Iter::new(&bytes, 20).unwrap();
+//! // and publish it in Multiboot format
+//! let mut buffer = [0u8; 48];
+//! let length = multiboot::e820::write_memory_map(entries, &mut buffer).unwrap();
+//! assert_eq!(length, 48);
+//! ```
+
+use core::convert::TryInto;
+
+use information::{MemoryEntry, MemoryType, PAddr, EXTENDED_ATTRIBUTE_ENABLED};
+
+/// Size of an entry without extended attributes.
+pub const ENTRY_SIZE: usize = 20;
+/// Size of an entry with ACPI 3.0 extended attributes.
+pub const EXTENDED_ENTRY_SIZE: usize = 24;
+
+/// A single entry of an E820 memory map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct E820Entry {
+    /// Start of the region.
+    pub base_addr: PAddr,
+    /// Length of the region in bytes.
+    pub length: u64,
+    /// Type of the region.
+    pub memory_type: MemoryType,
+    /// ACPI 3.0 extended attributes, if the entry is 24 bytes long.
+    pub extended_attributes: Option<u32>,
+}
+
+impl E820Entry {
+    /// Create a new 20 byte entry.
+    pub fn new(base_addr: PAddr, length: u64, memory_type: MemoryType) -> Self {
+        Self {
+            base_addr,
+            length,
+            memory_type,
+            extended_attributes: None,
+        }
+    }
+
+    /// Parse an entry from the start of `bytes`.
+    ///
+    /// Extended attributes are only read if `bytes` is at least 24 bytes long.
+    /// Returns `None` if it's shorter than 20 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < ENTRY_SIZE {
+            return None;
+        }
+        Some(Self {
+            base_addr: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            length: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            memory_type: u32::from_le_bytes(bytes[16..20].try_into().unwrap()).into(),
+            extended_attributes: bytes
+                .get(20..24)
+                .map(|attributes| u32::from_le_bytes(attributes.try_into().unwrap())),
+        })
+    }
+
+    /// Get the size of this entry in an E820 map, either 20 or 24 bytes.
+    pub fn size(&self) -> usize {
+        match self.extended_attributes {
+            Some(_) => EXTENDED_ENTRY_SIZE,
+            None => ENTRY_SIZE,
+        }
+    }
+
+    /// Write this entry to the start of `bytes`.
+    ///
+    /// If `bytes` is at least 24 bytes long, the extended attributes are written, too.
+    /// Entries without them get [`EXTENDED_ATTRIBUTE_ENABLED`] in that case,
+    /// just like the BIOS would report them.
+    ///
+    /// Returns the number of bytes written or `None` if `bytes` is too short.
+    ///
+    /// [`EXTENDED_ATTRIBUTE_ENABLED`]: ../information/constant.EXTENDED_ATTRIBUTE_ENABLED.html
+    pub fn write_to(&self, bytes: &mut [u8]) -> Option<usize> {
+        if bytes.len() < ENTRY_SIZE {
+            return None;
+        }
+        bytes[0..8].copy_from_slice(&self.base_addr.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.length.to_le_bytes());
+        bytes[16..20].copy_from_slice(&u32::from(self.memory_type).to_le_bytes());
+        match bytes.get_mut(20..24) {
+            Some(attributes) => {
+                let value = self
+                    .extended_attributes
+                    .unwrap_or(EXTENDED_ATTRIBUTE_ENABLED);
+                attributes.copy_from_slice(&value.to_le_bytes());
+                Some(EXTENDED_ENTRY_SIZE)
+            }
+            None => Some(ENTRY_SIZE),
+        }
+    }
+
+    /// Get this entry in the format of the Multiboot memory map.
+    ///
+    /// The extended attributes are dropped, use
+    /// [`write_memory_map`](fn.write_memory_map.html) to keep them.
+    pub fn to_memory_entry(&self) -> MemoryEntry {
+        MemoryEntry::new(self.base_addr, self.length, self.memory_type)
+    }
+}
+
+impl<'a> From<&'a MemoryEntry> for E820Entry {
+    fn from(entry: &'a MemoryEntry) -> Self {
+        Self::new(entry.base_address(), entry.length(), entry.memory_type())
+    }
+}
+
+impl From<MemoryEntry> for E820Entry {
+    fn from(entry: MemoryEntry) -> Self {
+        Self::from(&entry)
+    }
+}
+
+/// This is the item type of [`ExtendedMemoryMapIter`].
+///
+/// [`ExtendedMemoryMapIter`]: ../information/struct.ExtendedMemoryMapIter.html
+impl<'a> From<(&'a MemoryEntry, Option<u32>)> for E820Entry {
+    fn from((entry, extended_attributes): (&'a MemoryEntry, Option<u32>)) -> Self {
+        Self {
+            extended_attributes,
+            ..Self::from(entry)
+        }
+    }
+}
+
+/// Used to iterate over an E820 map in memory.
+#[derive(Debug, Clone)]
+pub struct E820Iter<'a> {
+    bytes: &'a [u8],
+    entry_size: usize,
+}
+
+impl<'a> E820Iter<'a> {
+    /// Iterate over the entries in `bytes` that are each `entry_size` bytes long.
+    ///
+    /// Returns `None` if `entry_size` is smaller than 20 bytes.
+    /// Trailing bytes that don't form a whole entry are ignored.
+    pub fn new(bytes: &'a [u8], entry_size: usize) -> Option<Self> {
+        if entry_size < ENTRY_SIZE {
+            return None;
+        }
+        Some(Self { bytes, entry_size })
+    }
+}
+
+impl<'a> Iterator for E820Iter<'a> {
+    type Item = E820Entry;
+
+    fn next(&mut self) -> Option<E820Entry> {
+        if self.bytes.len() < self.entry_size {
+            return None;
+        }
+        let (entry, rest) = self.bytes.split_at(self.entry_size);
+        self.bytes = rest;
+        // larger entries may contain fields we don't know about
+        E820Entry::from_bytes(&entry[..self.entry_size.min(EXTENDED_ENTRY_SIZE)])
+    }
+}
+
+/// Get the number of bytes a Multiboot memory map containing `entries` needs.
+pub fn memory_map_size<I: IntoIterator<Item = E820Entry>>(entries: I) -> usize {
+    entries.into_iter().map(|entry| 4 + entry.size()).sum()
+}
+
+/// Write `entries` into `buffer` in the format of the Multiboot memory map.
+///
+/// Each entry keeps its size, so entries with extended attributes
+/// can be read back using [`MemoryMapIter::with_extended_attributes`].
+///
+/// Returns the number of bytes written (`mmap_length`) or `None` if `buffer` is too small.
+///
+/// [`MemoryMapIter::with_extended_attributes`]: ../information/struct.MemoryMapIter.html#method.with_extended_attributes
+pub fn write_memory_map<I: IntoIterator<Item = E820Entry>>(
+    entries: I,
+    buffer: &mut [u8],
+) -> Option<usize> {
+    let mut offset = 0;
+    for entry in entries {
+        let size = entry.size();
+        let dst = buffer.get_mut(offset..offset + 4 + size)?;
+        dst[..4].copy_from_slice(&(size as u32).to_le_bytes());
+        entry.write_to(&mut dst[4..])?;
+        offset += 4 + size;
+    }
+    Some(offset)
+}
+
+/// Write `entries` into `buffer` as an E820 map with entries of `entry_size` bytes.
+///
+/// `entry_size` has to be either 20 or 24 bytes. Use 24 to keep the extended attributes.
+///
+/// Returns the number of entries written or `None` if `buffer` is too small.
+pub fn write_e820<I: IntoIterator<Item = E820Entry>>(
+    entries: I,
+    entry_size: usize,
+    buffer: &mut [u8],
+) -> Option<usize> {
+    if entry_size != ENTRY_SIZE && entry_size != EXTENDED_ENTRY_SIZE {
+        return None;
+    }
+    let mut count = 0;
+    for entry in entries {
+        let offset = count * entry_size;
+        entry.write_to(buffer.get_mut(offset..offset + entry_size)?)?;
+        count += 1;
+    }
+    Some(count)
+}
@@ -0,0 +1,334 @@
+//! This module contains simple physical frame allocators for early kernel code.
+//!
+//! Both allocators are seeded with the free memory reported by
+//! [`Multiboot::free_memory_regions`], so they never hand out memory that is
+//! still used by the boot information, the modules or the kernel itself.
+//!
+//! [`Multiboot::free_memory_regions`]: ../information/struct.Multiboot.html#method.free_memory_regions
+
+use core::ops::Range;
+
+use information::{Multiboot, PAddr, PhysicalMemoryMapper};
+
+/// Maximum number of free regions the [`BumpFrameAllocator`] keeps track of.
+///
+/// [`BumpFrameAllocator`]: struct.BumpFrameAllocator.html
+pub const MAX_REGIONS: usize = 64;
+
+/// Supported sizes of physical frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameSize {
+    /// A 4 KiB frame.
+    Small,
+    /// A 2 MiB frame.
+    Large,
+}
+
+impl FrameSize {
+    /// Get the size of the frame in bytes.
+    ///
+    /// Frames are always aligned to their size.
+    pub fn bytes(self) -> PAddr {
+        match self {
+            FrameSize::Small => 4096,
+            FrameSize::Large => 2 * 1024 * 1024,
+        }
+    }
+}
+
+/// Implemented by all physical frame allocators.
+pub trait FrameAllocator {
+    /// Allocate a frame of the given size.
+    ///
+    /// The returned address is aligned to the size of the frame.
+    fn allocate_frame(&mut self, size: FrameSize) -> Option<PAddr>;
+
+    /// Give a frame back to the allocator.
+    ///
+    /// `addr` and `size` must be the same as returned by and passed to
+    /// [`allocate_frame`](#tymethod.allocate_frame).
+    fn deallocate_frame(&mut self, addr: PAddr, size: FrameSize);
+}
+
+/// Allocator that hands out frames from the bottom of memory upwards.
+///
+/// Only the frame that was allocated last can be freed again,
+/// freeing any other frame leaks it.
+pub struct BumpFrameAllocator {
+    regions: [(PAddr, PAddr); MAX_REGIONS],
+    count: usize,
+    /// Index of the region we're currently allocating from.
+    current: usize,
+    /// The first address in the current region that hasn't been allocated yet.
+    next: PAddr,
+    /// Number of regions that didn't fit.
+    dropped: usize,
+}
+
+impl BumpFrameAllocator {
+    /// Create an allocator that manages all free memory of a Multiboot information.
+    ///
+    /// `reserved` contains additional ranges that may not be used,
+    /// e.g. the kernel image.
+    ///
+    /// Returns `None` if there is no memory map.
+    pub fn new<'a, 'b, M: PhysicalMemoryMapper + ?Sized>(
+        multiboot: &'a Multiboot<'a, 'b, M>,
+        reserved: &[Range<PAddr>],
+    ) -> Option<Self> {
+        multiboot
+            .free_memory_regions(reserved)
+            .map(Self::from_regions)
+    }
+
+    /// Create an allocator that manages the given free regions.
+    ///
+    /// The regions must not overlap, they are sorted by address.
+    /// Only the lowest [`MAX_REGIONS`] regions are used, the number of
+    /// regions that were left out is reported by [`dropped_regions`].
+    ///
+    /// [`MAX_REGIONS`]: constant.MAX_REGIONS.html
+    /// [`dropped_regions`]: #method.dropped_regions
+    pub fn from_regions<I: IntoIterator<Item = Range<PAddr>>>(regions: I) -> Self {
+        let mut allocator = Self {
+            regions: [(0, 0); MAX_REGIONS],
+            count: 0,
+            current: 0,
+            next: 0,
+            dropped: 0,
+        };
+        for region in regions.into_iter().filter(|r| r.start < r.end) {
+            let index =
+                allocator.regions[..allocator.count].partition_point(|r| r.0 < region.start);
+            if allocator.count == MAX_REGIONS {
+                allocator.dropped += 1;
+                if index == MAX_REGIONS {
+                    continue;
+                }
+            } else {
+                allocator.count += 1;
+            }
+            allocator
+                .regions
+                .copy_within(index..allocator.count - 1, index + 1);
+            allocator.regions[index] = (region.start, region.end);
+        }
+        allocator.next = allocator.regions[0].0;
+        allocator
+    }
+
+    /// Get the number of free regions that were ignored because there
+    /// were more than [`MAX_REGIONS`].
+    ///
+    /// [`MAX_REGIONS`]: constant.MAX_REGIONS.html
+    pub fn dropped_regions(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl FrameAllocator for BumpFrameAllocator {
+    fn allocate_frame(&mut self, size: FrameSize) -> Option<PAddr> {
+        let bytes = size.bytes();
+        let mut next = self.next;
+        for index in self.current..self.count {
+            let (start, end) = self.regions[index];
+            let frame = round_up!(next.max(start), bytes);
+            if frame
+                .checked_add(bytes)
+                .is_some_and(|frame_end| frame_end <= end)
+            {
+                // everything we skipped is lost for good
+                self.current = index;
+                self.next = frame + bytes;
+                return Some(frame);
+            }
+            next = end;
+        }
+        None
+    }
+
+    fn deallocate_frame(&mut self, addr: PAddr, size: FrameSize) {
+        if self.current < self.count && addr + size.bytes() == self.next {
+            self.next = addr;
+        }
+    }
+}
+
+/// Allocator that keeps track of every 4 KiB frame in a bitmap.
+///
+/// The bitmap itself is placed in the first free region that is large enough.
+pub struct BitmapFrameAllocator<'m> {
+    /// One bit per frame, set if the frame is free.
+    bitmap: &'m mut [u8],
+    /// Where the bitmap is located in physical memory.
+    bitmap_addr: PAddr,
+    /// The address of the frame described by the first bit.
+    base: PAddr,
+    /// Index of the byte to start searching at.
+    next: usize,
+}
+
+impl<'m> BitmapFrameAllocator<'m> {
+    /// Create an allocator that manages all free memory of a Multiboot information.
+    ///
+    /// `reserved` contains additional ranges that may not be used,
+    /// e.g. the kernel image. `map` has to make the given physical range
+    /// accessible, it's called exactly once for the bitmap.
+    ///
+    /// Returns `None` if there is no memory map or no region is large enough
+    /// to hold the bitmap.
+    ///
+    /// # Safety
+    /// The free memory reported by `multiboot` must actually be unused
+    /// and `map` must return a slice that refers to the given physical memory.
+    pub unsafe fn new<'a, 'b, M, F>(
+        multiboot: &'a Multiboot<'a, 'b, M>,
+        reserved: &[Range<PAddr>],
+        map: F,
+    ) -> Option<Self>
+    where
+        M: PhysicalMemoryMapper + ?Sized,
+        F: FnOnce(PAddr, usize) -> &'m mut [u8],
+    {
+        multiboot.free_memory_regions(reserved)?;
+        Self::init(
+            || {
+                multiboot
+                    .free_memory_regions(reserved)
+                    .into_iter()
+                    .flatten()
+            },
+            map,
+        )
+    }
+
+    /// Create an allocator that manages the given free regions.
+    ///
+    /// The regions must not overlap, they are aligned inward to 4 KiB.
+    ///
+    /// # Safety
+    /// The regions must actually be unused and `map` must return a slice
+    /// that refers to the given physical memory.
+    pub unsafe fn from_regions<F>(regions: &[Range<PAddr>], map: F) -> Option<Self>
+    where
+        F: FnOnce(PAddr, usize) -> &'m mut [u8],
+    {
+        Self::init(|| regions.iter().cloned(), map)
+    }
+
+    unsafe fn init<R, I, F>(regions: R, map: F) -> Option<Self>
+    where
+        R: Fn() -> I,
+        I: Iterator<Item = Range<PAddr>>,
+        F: FnOnce(PAddr, usize) -> &'m mut [u8],
+    {
+        let small = FrameSize::Small.bytes();
+        let large = FrameSize::Large.bytes();
+        let aligned = || {
+            regions()
+                .map(move |r| round_up!(r.start, small)..round_down!(r.end, small))
+                .filter(|r| r.start < r.end)
+        };
+        // the bitmap covers whole large frames, so they can be found easily
+        let base = round_down!(aligned().map(|r| r.start).min()?, large);
+        let end = round_up!(aligned().map(|r| r.end).max()?, large);
+        let length = ((end - base) / small / 8) as usize;
+        let bitmap_bytes = round_up!(length as PAddr, small);
+        let bitmap_addr = aligned().find(|r| r.end - r.start >= bitmap_bytes)?.start;
+
+        let bitmap = &mut map(bitmap_addr, length)[..length];
+        for byte in bitmap.iter_mut() {
+            *byte = 0;
+        }
+        let mut allocator = Self {
+            bitmap,
+            bitmap_addr,
+            base,
+            next: 0,
+        };
+        for region in aligned() {
+            allocator.set_range(region, true);
+        }
+        allocator.set_range(bitmap_addr..bitmap_addr + bitmap_bytes, false);
+        Some(allocator)
+    }
+
+    /// Mark all frames in `range` as free or used.
+    ///
+    /// Frames outside of the bitmap are ignored.
+    fn set_range(&mut self, range: Range<PAddr>, free: bool) {
+        let small = FrameSize::Small.bytes();
+        let frames = self.bitmap.len() as PAddr * 8;
+        let first = range.start.saturating_sub(self.base) / small;
+        let last = (range.end.saturating_sub(self.base) / small).min(frames);
+        for frame in first..last {
+            let (byte, bit) = ((frame / 8) as usize, frame % 8);
+            if free {
+                self.bitmap[byte] |= 1 << bit;
+            } else {
+                self.bitmap[byte] &= !(1 << bit);
+            }
+        }
+    }
+
+    /// Get the physical range that is used by the bitmap.
+    pub fn bitmap_region(&self) -> Range<PAddr> {
+        let length = round_up!(self.bitmap.len() as PAddr, FrameSize::Small.bytes());
+        self.bitmap_addr..self.bitmap_addr + length
+    }
+
+    /// Count the free 4 KiB frames.
+    pub fn free_frames(&self) -> usize {
+        self.bitmap.iter().map(|b| b.count_ones() as usize).sum()
+    }
+}
+
+impl<'m> FrameAllocator for BitmapFrameAllocator<'m> {
+    fn allocate_frame(&mut self, size: FrameSize) -> Option<PAddr> {
+        let small = FrameSize::Small.bytes();
+        // number of bytes in the bitmap per frame
+        let stride = match size {
+            FrameSize::Small => 1,
+            FrameSize::Large => (FrameSize::Large.bytes() / small / 8) as usize,
+        };
+        let chunks = self.bitmap.len() / stride;
+        let first = self.next / stride;
+        let index = (first..chunks).chain(0..first).find(|&chunk| {
+            let bytes = &self.bitmap[chunk * stride..(chunk + 1) * stride];
+            match size {
+                FrameSize::Small => bytes[0] != 0,
+                FrameSize::Large => bytes.iter().all(|&b| b == 0xff),
+            }
+        })?;
+        let frame = match size {
+            FrameSize::Small => {
+                let bit = self.bitmap[index].trailing_zeros() as PAddr;
+                index as PAddr * 8 + bit
+            }
+            FrameSize::Large => (index * stride * 8) as PAddr,
+        };
+        let addr = self.base + frame * small;
+        self.set_range(addr..addr + size.bytes(), false);
+        self.next = index * stride;
+        Some(addr)
+    }
+
+    /// Frames that are not managed by this allocator, misaligned or
+    /// overlap the bitmap are ignored.
+    fn deallocate_frame(&mut self, addr: PAddr, size: FrameSize) {
+        let frames = self.bitmap.len() as PAddr * 8;
+        let end = match addr.checked_add(size.bytes()) {
+            Some(end) => end,
+            None => return,
+        };
+        let bitmap = self.bitmap_region();
+        if round_down!(addr, size.bytes()) != addr
+            || addr < self.base
+            || (end - self.base) / FrameSize::Small.bytes() > frames
+            || (addr < bitmap.end && bitmap.start < end)
+        {
+            return;
+        }
+        self.set_range(addr..end, true);
+    }
+}
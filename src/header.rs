@@ -6,6 +6,10 @@
 
 use core::convert::TryInto;
 use core::fmt;
+use core::ops::Range;
+
+use information::PAddr;
+use module_kind::EM_386;
 
 pub const MULTIBOOT_HEADER_MAGIC: u32 = 0x1BADB002;
 
@@ -226,3 +230,228 @@ pub enum VideoModeType {
     LinearGraphics,
     TextMode,
 }
+
+/// Maximum number of segments a [`LoadPlan`] can hold.
+///
+/// [`LoadPlan`]: struct.LoadPlan.html
+pub const MAX_LOAD_SEGMENTS: usize = 16;
+
+/// Legacy GRUB behavior that some old kernels depend on.
+///
+/// These correspond to GRUB's `--quirk-*` options of the `multiboot` command.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Quirks {
+    /// Place the modules right after the end of the kernel (including its bss)
+    /// instead of anywhere in free memory (`--quirk-modules-after-kernel`).
+    pub modules_after_kernel: bool,
+    /// Load ELF images from their program headers, even if they also have
+    /// the address fields set (`--quirk-bad-kludge`).
+    pub bad_kludge: bool,
+}
+
+/// Type of ELF program headers that have to be loaded.
+const PT_LOAD: u32 = 1;
+/// ELF type of executables.
+const ET_EXEC: u16 = 2;
+
+/// A part of the kernel image that has to be copied to physical memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoadSegment {
+    /// Offset of the data in the image.
+    pub offset: u32,
+    /// Number of bytes to copy from the image.
+    pub file_size: u32,
+    /// Physical address to copy the data to.
+    pub address: u32,
+    /// Size in memory, everything after `file_size` is zeroed (bss).
+    pub memory_size: u32,
+}
+
+impl LoadSegment {
+    /// Get the physical memory occupied by this segment.
+    pub fn range(&self) -> Range<PAddr> {
+        let start = PAddr::from(self.address);
+        start..start + PAddr::from(self.memory_size)
+    }
+}
+
+/// Describes how a kernel image is loaded and started.
+///
+/// This is either derived from the address fields of the Multiboot header
+/// (the "a.out kludge") or from the ELF program headers.
+#[derive(Debug, Copy, Clone)]
+pub struct LoadPlan {
+    segments: [LoadSegment; MAX_LOAD_SEGMENTS],
+    count: usize,
+    entry: u32,
+}
+
+impl LoadPlan {
+    /// Determine how to load `image`, whose Multiboot header is `header`.
+    ///
+    /// The address fields are used if present, otherwise the image has to be an ELF.
+    /// Returns `None` if the image is malformed or has too many segments.
+    pub fn new(header: &Header, image: &[u8]) -> Option<Self> {
+        Self::with_quirks(header, image, Quirks::default())
+    }
+
+    /// Like [`new`](#method.new), but emulate the given GRUB quirks.
+    ///
+    /// With [`Quirks::bad_kludge`], ELF images are loaded using their program
+    /// headers even if the address fields are present. The address fields are
+    /// still used for ELFs that [`from_elf`](#method.from_elf) can't load.
+    ///
+    /// [`Quirks::bad_kludge`]: struct.Quirks.html#structfield.bad_kludge
+    pub fn with_quirks(header: &Header, image: &[u8], quirks: Quirks) -> Option<Self> {
+        if !header.has_multiboot_addresses() {
+            return Self::from_elf(image);
+        }
+        if quirks.bad_kludge && image.starts_with(b"\x7fELF") {
+            if let Some(plan) = Self::from_elf(image) {
+                return Some(plan);
+            }
+        }
+        Self::from_addresses(header, image)
+    }
+
+    /// Use the address fields of the Multiboot header.
+    ///
+    /// A `load_end_address` of 0 means the rest of the image is loaded,
+    /// a `bss_end_address` of 0 means there is no bss.
+    pub fn from_addresses(header: &Header, image: &[u8]) -> Option<Self> {
+        if !header.has_multiboot_addresses() {
+            return None;
+        }
+        let addresses = header.header.addresses;
+        let header_offset = addresses
+            .header_address
+            .checked_sub(addresses.load_address)?;
+        let offset = header.header_start.checked_sub(header_offset)?;
+        let available: u32 = image.len().checked_sub(offset as usize)?.try_into().ok()?;
+        let file_size = match addresses.load_end_address {
+            0 => available,
+            end => end.checked_sub(addresses.load_address)?,
+        };
+        if file_size > available {
+            return None;
+        }
+        let memory_size = match addresses.bss_end_address {
+            0 => file_size,
+            end => end.checked_sub(addresses.load_address)?.max(file_size),
+        };
+        let mut plan = Self::empty(addresses.entry_address);
+        plan.push(LoadSegment {
+            offset,
+            file_size,
+            address: addresses.load_address,
+            memory_size,
+        })?;
+        Some(plan)
+    }
+
+    /// Use the program headers of a 32-bit x86 ELF executable.
+    ///
+    /// Segments are loaded at their physical address. Like GRUB, an entry point
+    /// that lies within a segment is translated from its virtual to its
+    /// physical address.
+    pub fn from_elf(image: &[u8]) -> Option<Self> {
+        let u16_at = |offset: usize| -> Option<u16> {
+            Some(u16::from_le_bytes(
+                image.get(offset..offset + 2)?.try_into().unwrap(),
+            ))
+        };
+        let u32_at = |offset: usize| -> Option<u32> {
+            Some(u32::from_le_bytes(
+                image.get(offset..offset + 4)?.try_into().unwrap(),
+            ))
+        };
+        // 32 bit, little endian
+        if image.get(0..6)? != b"\x7fELF\x01\x01" || u16_at(16)? != ET_EXEC || u16_at(18)? != EM_386
+        {
+            return None;
+        }
+        let mut entry = u32_at(24)?;
+        let ph_offset = u32_at(28)? as usize;
+        let ph_size = usize::from(u16_at(42)?);
+        let ph_count = usize::from(u16_at(44)?);
+        if ph_size < 32 {
+            return None;
+        }
+        let mut plan = Self::empty(entry);
+        let mut translated = false;
+        for index in 0..ph_count {
+            let ph = ph_offset.checked_add(index.checked_mul(ph_size)?)?;
+            if u32_at(ph)? != PT_LOAD {
+                continue;
+            }
+            let offset = u32_at(ph + 4)?;
+            let virtual_address = u32_at(ph + 8)?;
+            let address = u32_at(ph + 12)?;
+            let file_size = u32_at(ph + 16)?;
+            let memory_size = u32_at(ph + 20)?;
+            if file_size > memory_size
+                || image.len().saturating_sub(offset as usize) < file_size as usize
+                || address.checked_add(memory_size).is_none()
+            {
+                return None;
+            }
+            if memory_size == 0 {
+                continue;
+            }
+            if !translated
+                && (virtual_address..virtual_address.wrapping_add(memory_size)).contains(&entry)
+            {
+                entry = entry.wrapping_sub(virtual_address).wrapping_add(address);
+                translated = true;
+            }
+            plan.push(LoadSegment {
+                offset,
+                file_size,
+                address,
+                memory_size,
+            })?;
+        }
+        plan.entry = entry;
+        match plan.count {
+            0 => None,
+            _ => Some(plan),
+        }
+    }
+
+    fn empty(entry: u32) -> Self {
+        let segment = LoadSegment {
+            offset: 0,
+            file_size: 0,
+            address: 0,
+            memory_size: 0,
+        };
+        Self {
+            segments: [segment; MAX_LOAD_SEGMENTS],
+            count: 0,
+            entry,
+        }
+    }
+
+    fn push(&mut self, segment: LoadSegment) -> Option<()> {
+        *self.segments.get_mut(self.count)? = segment;
+        self.count += 1;
+        Some(())
+    }
+
+    /// Get the segments that have to be loaded.
+    pub fn segments(&self) -> &[LoadSegment] {
+        &self.segments[..self.count]
+    }
+
+    /// Get the physical address of the entry point.
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    /// Get the physical memory spanned by the kernel, from the lowest to the highest segment.
+    pub fn range(&self) -> Range<PAddr> {
+        let start = self.segments().iter().map(|s| s.range().start).min();
+        let end = self.segments().iter().map(|s| s.range().end).max();
+        start.unwrap_or(0)..end.unwrap_or(0)
+    }
+}
@@ -4,23 +4,40 @@
 //!
 //! [`Multiboot`]: struct.Multiboot.html
 
-use core::cmp;
-use core::convert::TryInto;
+use core::cmp::Ordering;
+use core::convert::{TryFrom, TryInto};
 use core::fmt;
 use core::fmt::Debug;
-use core::mem::{size_of, transmute};
+use core::mem::{size_of, size_of_val, transmute};
+use core::ops::Range;
+use core::ptr;
 use core::slice;
 use core::str;
 
+use cmdline::{CommandLine, Word};
+use e820::{self, E820Entry};
+use header::Header;
+use loader::{self, LoadError, ModuleSource, MODULE_ALIGNMENT};
+use module_kind::{ModuleKind, DETECT_LENGTH};
+
+#[cfg(feature = "decompress")]
+use decompress::{self, DecompressError};
+
 /// Value found in %eax after multiboot jumps to our entry point.
 pub const SIGNATURE_EAX: u32 = 0x2BADB002;
 
 pub type PAddr = u64;
 
-/// Implement this trait to be able to get or set fields containing a pointer.
+/// Granularity to which free memory regions are aligned.
+const PAGE_SIZE: PAddr = 4096;
+
+/// Implement this trait to be able to get fields containing a pointer.
+///
+/// Memory translation happens here. This is all a kernel that only reads
+/// the information needs, to also set fields implement [`MemoryAllocator`].
 ///
-/// Memory translation, allocation and deallocation happens here.
-pub trait MemoryManagement {
+/// [`MemoryAllocator`]: trait.MemoryAllocator.html
+pub trait PhysicalMemoryMapper {
     /// Translates physical addr + size into a kernel accessible slice.
     ///
     /// The simplest paddr_to_slice function would for example be just the
@@ -35,38 +52,295 @@ pub trait MemoryManagement {
     /// querying/knowledge about page-table setup. Also might want to verify
     /// that multiboot information is actually valid.
     unsafe fn paddr_to_slice(&self, addr: PAddr, length: usize) -> Option<&'static [u8]>;
+}
+
+impl<M: PhysicalMemoryMapper + ?Sized> PhysicalMemoryMapper for &M {
+    unsafe fn paddr_to_slice(&self, addr: PAddr, length: usize) -> Option<&'static [u8]> {
+        (**self).paddr_to_slice(addr, length)
+    }
+}
 
+impl<M: PhysicalMemoryMapper + ?Sized> PhysicalMemoryMapper for &mut M {
+    unsafe fn paddr_to_slice(&self, addr: PAddr, length: usize) -> Option<&'static [u8]> {
+        (**self).paddr_to_slice(addr, length)
+    }
+}
+
+/// Implement this trait (and [`PhysicalMemoryMapper`]) to be able to set fields containing a pointer.
+///
+/// Memory allocation and deallocation happens here.
+///
+/// [`PhysicalMemoryMapper`]: trait.PhysicalMemoryMapper.html
+pub trait MemoryAllocator {
     /// Allocates `length` bytes.
     ///
     /// The returned tuple consists of the physical address (that goes into the struct)
     /// and the slice which to use to write to.
     ///
-    /// If you only want to read fields, this can just always return `None`.
-    ///
     /// # Safety
     /// Lifetime of buffer should be >= self.
     unsafe fn allocate(&mut self, length: usize) -> Option<(PAddr, &mut [u8])>;
 
+    /// Allocates `length` bytes at a physical address that is a multiple of `align`.
+    ///
+    /// `align` is a power of two. The default implementation calls [`allocate`]
+    /// and fails if the result happens to be misaligned, so override this
+    /// if your allocator can do better.
+    ///
+    /// [`allocate`]: #tymethod.allocate
+    ///
+    /// # Safety
+    /// Same as [`allocate`].
+    unsafe fn allocate_aligned(
+        &mut self,
+        length: usize,
+        align: usize,
+    ) -> Option<(PAddr, &mut [u8])> {
+        let (addr, buffer) = self.allocate(length)?;
+        let buffer: *mut [u8] = buffer;
+        if addr & (align as PAddr - 1) == 0 {
+            return Some((addr, &mut *buffer));
+        }
+        self.deallocate(addr);
+        None
+    }
+
+    /// Allocates memory as described by `request`.
+    ///
+    /// This is what the crate calls. The default implementation calls [`allocate`]
+    /// if the request only asks for the alignment Multiboot recommends (see
+    /// [`AllocationRequest::new`]) and [`allocate_aligned`] otherwise. It fails if
+    /// the result ends above `request.max_address`, so override this if your
+    /// allocator can place memory.
+    ///
+    /// [`allocate`]: #tymethod.allocate
+    /// [`AllocationRequest::new`]: struct.AllocationRequest.html#method.new
+    /// [`allocate_aligned`]: #method.allocate_aligned
+    ///
+    /// # Safety
+    /// Same as [`allocate`](#tymethod.allocate).
+    unsafe fn allocate_request(
+        &mut self,
+        request: AllocationRequest,
+    ) -> Option<(PAddr, &mut [u8])> {
+        let recommended = AllocationRequest::new(request.size, request.purpose).align;
+        let (addr, buffer) = if request.align <= recommended {
+            self.allocate(request.size)?
+        } else {
+            self.allocate_aligned(request.size, request.align)?
+        };
+        let buffer: *mut [u8] = buffer;
+        if addr.saturating_add(request.size as PAddr) <= request.max_address {
+            return Some((addr, &mut *buffer));
+        }
+        self.deallocate_sized(addr, request.size);
+        None
+    }
+
     /// Free the previously allocated memory.
     ///
     /// This should handle null pointers by doing nothing.
     ///
-    /// If you only want to read fields, this can just always panic.
-    ///
     /// # Safety
     /// TBD.
     unsafe fn deallocate(&mut self, addr: PAddr);
+
+    /// Free `size` bytes of previously allocated memory.
+    ///
+    /// This is what the crate calls, `size` is the size of the allocation.
+    /// The default implementation calls [`deallocate`](#tymethod.deallocate).
+    ///
+    /// # Safety
+    /// Same as [`deallocate`](#tymethod.deallocate).
+    unsafe fn deallocate_sized(&mut self, addr: PAddr, _size: usize) {
+        self.deallocate(addr)
+    }
+}
+
+/// Memory translation, allocation and deallocation.
+///
+/// This is needed to set fields containing a pointer and is implemented
+/// for everything that implements both [`PhysicalMemoryMapper`] and [`MemoryAllocator`].
+///
+/// [`PhysicalMemoryMapper`]: trait.PhysicalMemoryMapper.html
+/// [`MemoryAllocator`]: trait.MemoryAllocator.html
+pub trait MemoryManagement: PhysicalMemoryMapper + MemoryAllocator {}
+
+impl<T: PhysicalMemoryMapper + MemoryAllocator + ?Sized> MemoryManagement for T {}
+
+/// What an allocation is used for.
+///
+/// This is part of an [`AllocationRequest`], allocators may use it
+/// to place different kinds of data in different areas.
+///
+/// [`AllocationRequest`]: struct.AllocationRequest.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AllocationPurpose {
+    /// The [`MultibootInfo`] struct.
+    ///
+    /// [`MultibootInfo`]: struct.MultibootInfo.html
+    Info,
+    /// A zero terminated string.
+    String,
+    /// The module table.
+    ModuleTable,
+    /// The contents of a module.
+    Module,
+    /// The memory map.
+    MemoryMap,
+}
+
+/// A request for memory, see [`MemoryAllocator::allocate_request`].
+///
+/// [`MemoryAllocator::allocate_request`]: trait.MemoryAllocator.html#method.allocate_request
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AllocationRequest {
+    /// The number of bytes.
+    pub size: usize,
+    /// The alignment of the address, a power of two.
+    pub align: usize,
+    /// The allocation has to end at or below this address.
+    pub max_address: PAddr,
+    /// What the memory is used for.
+    pub purpose: AllocationPurpose,
+}
+
+impl AllocationRequest {
+    /// Request `size` bytes below 4 GiB with the alignment Multiboot needs for `purpose`.
+    ///
+    /// Everything except strings and modules is aligned to 4 bytes.
+    pub fn new(size: usize, purpose: AllocationPurpose) -> Self {
+        let align = match purpose {
+            AllocationPurpose::String | AllocationPurpose::Module => 1,
+            _ => 4,
+        };
+        Self {
+            size,
+            align,
+            max_address: 1 << 32,
+            purpose,
+        }
+    }
+
+    /// Change the alignment, which has to be a power of two.
+    pub fn align(mut self, align: usize) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Change the address the allocation has to end at or below.
+    pub fn max_address(mut self, max_address: PAddr) -> Self {
+        self.max_address = max_address;
+        self
+    }
 }
 
 /// Multiboot struct clients mainly interact with
 ///
 /// To create this use [`Multiboot::from_ptr`] or [`Multiboot::from_ref`].
 ///
+/// The setters only free memory that was allocated through the same instance,
+/// so editing information that was passed by a bootloader never frees its memory.
+/// Editing a module table that was passed in copies it and its strings first.
+///
+/// `M` only has to implement [`PhysicalMemoryMapper`] to read the information,
+/// setters that allocate are available if it also implements [`MemoryAllocator`].
+///
 /// [`Multiboot::from_ptr`]: struct.Multiboot.html#method.from_ptr
 /// [`Multiboot::from_ref`]: struct.Multiboot.html#method.from_ref
-pub struct Multiboot<'a, 'b> {
+/// [`PhysicalMemoryMapper`]: trait.PhysicalMemoryMapper.html
+/// [`MemoryAllocator`]: trait.MemoryAllocator.html
+pub struct Multiboot<'a, 'b, M: ?Sized + 'b = dyn MemoryManagement + 'b> {
     header: &'a mut MultibootInfo,
-    memory_management: &'b mut dyn MemoryManagement,
+    /// Physical address of `header`, if it is known.
+    mboot_ptr: Option<PAddr>,
+    memory_management: &'b mut M,
+    /// What may be freed.
+    owned: OwnedAllocations,
+    /// How to free it, this is set when allocating.
+    release: Option<unsafe fn(&mut M, PAddr, usize)>,
+}
+
+/// The memory a [`Multiboot`] allocated itself and may free again.
+///
+/// Each field is the size of the allocation the corresponding pointer refers to,
+/// or `None` if it was passed in. A table that was passed in is copied with its
+/// strings before editing it.
+///
+/// [`Multiboot`]: struct.Multiboot.html
+#[derive(Default)]
+struct OwnedAllocations {
+    cmdline: Option<usize>,
+    boot_loader_name: Option<usize>,
+    module_table: Option<OwnedModuleTable>,
+    memory_map: Option<usize>,
+}
+
+/// A module table that was allocated by a [`Multiboot`].
+///
+/// Its strings were allocated as well, except for those that couldn't be copied
+/// from a table that was passed in. These are marked in a bitmap after the entries.
+///
+/// [`Multiboot`]: struct.Multiboot.html
+#[derive(Clone, Copy)]
+struct OwnedModuleTable {
+    /// The number of entries there is room for.
+    capacity: usize,
+    /// Whether there is a bitmap of strings that weren't allocated here.
+    foreign_strings: bool,
+}
+
+impl OwnedModuleTable {
+    /// The size of the bitmap for `capacity` entries.
+    fn bitmap_size(capacity: usize) -> usize {
+        capacity / 8 + 1
+    }
+
+    /// The size of the allocation.
+    fn size(&self) -> usize {
+        let bitmap = if self.foreign_strings {
+            Self::bitmap_size(self.capacity)
+        } else {
+            0
+        };
+        self.capacity * size_of::<MBModule>() + bitmap
+    }
+}
+
+/// A module table that was allocated, but isn't used yet.
+struct NewModuleTable<'a> {
+    addr: u32,
+    entries: &'a mut [MBModule],
+    /// A bit for each entry whose string wasn't allocated here, may be empty.
+    foreign_strings: &'a mut [u8],
+}
+
+impl<'a> NewModuleTable<'a> {
+    /// Check whether the string of the entry at `index` wasn't allocated here.
+    fn is_foreign(&self, index: usize) -> bool {
+        self.foreign_strings
+            .get(index / 8)
+            .is_some_and(|byte| byte & (1 << (index % 8)) != 0)
+    }
+
+    /// Mark the string of the entry at `index` as (not) allocated here.
+    ///
+    /// There has to be a bitmap to mark a string as foreign.
+    fn set_foreign(&mut self, index: usize, foreign: bool) {
+        match self.foreign_strings.get_mut(index / 8) {
+            Some(byte) if foreign => *byte |= 1 << (index % 8),
+            Some(byte) => *byte &= !(1 << (index % 8)),
+            None => assert!(!foreign, "no bitmap for foreign strings"),
+        }
+    }
+
+    /// How this table is owned once it is used.
+    fn owned(&self) -> OwnedModuleTable {
+        OwnedModuleTable {
+            capacity: self.entries.len(),
+            foreign_strings: !self.foreign_strings.is_empty(),
+        }
+    }
 }
 
 /// Representation of Multiboot Information according to specification.
@@ -145,10 +419,10 @@ pub struct MultibootInfo {
 
     boot_loader_name: u32,
 
-    _apm_table: u32,
+    apm_table: u32,
 
-    _vbe_control_info: u32,
-    _vbe_mode_info: u32,
+    vbe_control_info: u32,
+    vbe_mode_info: u32,
     _vbe_mode: u16,
     _vbe_interface_seg: u16,
     _vbe_interface_off: u16,
@@ -157,8 +431,39 @@ pub struct MultibootInfo {
     framebuffer_table: FramebufferTable,
 }
 
+/// Get the length of a C string (without the terminating zero).
+///
+/// The string ends at the first zero or unmapped byte.
+unsafe fn c_string_len<M: PhysicalMemoryMapper + ?Sized>(
+    memory_management: &M,
+    string: PAddr,
+) -> Option<usize> {
+    if string == 0 {
+        return None;
+    }
+    let mut len = 0;
+    let mut ptr = string;
+    while let Some(byte) = memory_management.paddr_to_slice(ptr, 1) {
+        if byte == [0] {
+            break;
+        }
+        ptr += 1;
+        len += 1;
+    }
+    Some(len)
+}
+
+/// Get the bytes of a C string (without the terminating zero).
+unsafe fn c_string_bytes<M: PhysicalMemoryMapper + ?Sized>(
+    memory_management: &M,
+    string: PAddr,
+) -> Option<&'static [u8]> {
+    c_string_len(memory_management, string)
+        .and_then(|len| memory_management.paddr_to_slice(string, len))
+}
+
 /// Multiboot structure.
-impl<'a, 'b> Multiboot<'a, 'b> {
+impl<'a, 'b, M: PhysicalMemoryMapper + ?Sized> Multiboot<'a, 'b, M> {
     /// Initializes the multiboot structure from a passed address.
     ///
     /// This is the way to go, if you're writing a kernel.
@@ -168,25 +473,35 @@ impl<'a, 'b> Multiboot<'a, 'b> {
     ///  * `mboot_ptr` - The physical address of the multiboot header. On qemu for example
     ///                  this is typically at 0x9500.
     ///  * `memory_management` - Translation of the physical addresses into kernel addresses,
-    ///                          allocation and deallocation of memory.
-    ///                          See the [`MemoryManagement`] description for more details.
+    ///                          see the [`PhysicalMemoryMapper`] description for more details.
+    ///                          Setting fields that point to newly allocated memory also
+    ///                          requires a [`MemoryAllocator`].
+    ///
+    /// Returns `None` if `mboot_ptr` is 0 or can't be translated.
     ///
     /// # Safety
     /// The user must ensure that mboot_ptr holds the physical address of a valid
     /// Multiboot1 structure and that memory management provides correct translations.
     ///
-    /// [`MemoryManagement`]: trait.MemoryManagement.html
+    /// [`PhysicalMemoryMapper`]: trait.PhysicalMemoryMapper.html
+    /// [`MemoryAllocator`]: trait.MemoryAllocator.html
     pub unsafe fn from_ptr(
         mboot_ptr: PAddr,
-        memory_management: &'b mut dyn MemoryManagement,
-    ) -> Option<Multiboot<'a, 'b>> {
+        memory_management: &'b mut M,
+    ) -> Option<Multiboot<'a, 'b, M>> {
+        if mboot_ptr == 0 {
+            return None;
+        }
         memory_management
             .paddr_to_slice(mboot_ptr, size_of::<MultibootInfo>())
             .map(move |inner| {
                 let info = &mut *(inner.as_ptr() as *mut MultibootInfo);
                 Multiboot {
                     header: info,
+                    mboot_ptr: Some(mboot_ptr),
                     memory_management,
+                    owned: OwnedAllocations::default(),
+                    release: None,
                 }
             })
     }
@@ -199,21 +514,24 @@ impl<'a, 'b> Multiboot<'a, 'b> {
     ///
     ///  * `info` - The (mutable) reference to a [`MultibootInfo`] struct.
     ///  * `memory_management` - Translation of the physical addresses into kernel addresses,
-    ///                          allocation and deallocation of memory.
-    ///                          See the [`MemoryManagement`] description for more details.
+    ///                          see the [`PhysicalMemoryMapper`] description for more details.
+    ///                          Setting fields that point to newly allocated memory also
+    ///                          requires a [`MemoryAllocator`].
     ///
     /// # Safety
     /// The user must ensure that the memory management can allocate memory.
     ///
     /// [`MultibootInfo`]: struct.MultibootInfo.html
     /// [`MultibootInfo::default`]: struct.MultibootInfo.html#impl-Default
-    pub fn from_ref(
-        info: &'a mut MultibootInfo,
-        memory_management: &'b mut dyn MemoryManagement,
-    ) -> Self {
+    /// [`PhysicalMemoryMapper`]: trait.PhysicalMemoryMapper.html
+    /// [`MemoryAllocator`]: trait.MemoryAllocator.html
+    pub fn from_ref(info: &'a mut MultibootInfo, memory_management: &'b mut M) -> Self {
         Self {
             header: info,
+            mboot_ptr: None,
             memory_management,
+            owned: OwnedAllocations::default(),
+            release: None,
         }
     }
 
@@ -224,41 +542,41 @@ impl<'a, 'b> Multiboot<'a, 'b> {
     }
 
     /// Convert a C string into a u8 slice and from there into a &str.
-    /// This unsafe block builds on assumption that multiboot strings are sane.
+    ///
+    /// Returns `None` if the string isn't valid UTF-8.
     unsafe fn convert_c_string(&self, string: PAddr) -> Option<&'a str> {
-        if string == 0 {
-            return None;
-        }
-        let mut len = 0;
-        let mut ptr = string;
-        while let Some(byte) = self.memory_management.paddr_to_slice(ptr, 1) {
-            if byte == [0] {
-                break;
-            }
-            ptr += 1;
-            len += 1;
-        }
-        self.memory_management
-            .paddr_to_slice(string, len)
-            .map(|slice| str::from_utf8_unchecked(slice))
+        c_string_bytes(self.memory_management, string).and_then(|slice| str::from_utf8(slice).ok())
+    }
+
+    /// Get a module from an entry of the module table.
+    unsafe fn convert_module(&self, entry: &MBModule) -> Module<'a> {
+        Module::from_bytes(
+            entry.start.into(),
+            entry.end.into(),
+            c_string_bytes(self.memory_management, entry.string.into()),
+        )
+    }
+
+    /// Get the length of a C string (without the terminating zero).
+    unsafe fn c_string_len(&self, string: PAddr) -> Option<usize> {
+        c_string_len(self.memory_management, string)
     }
 
-    /// Convert a &str into a u8 slice and from there into a C string.
+    /// Get the physical range of a C string (including the terminating zero).
+    unsafe fn c_string_range(&self, string: PAddr) -> Option<Range<PAddr>> {
+        self.c_string_len(string)
+            .map(|len| string..string + len as PAddr + 1)
+    }
+
+    /// Free memory that was allocated by a setter.
     ///
-    /// This unsafe block requires the possibility to allocate memory
-    /// (and assumes that this memory can be addresses using an u32).
-    unsafe fn convert_to_c_string(&mut self, string: Option<&str>) -> u32 {
-        match string {
-            Some(s) => {
-                let bytes = s.bytes();
-                let len = bytes.len();
-                let (addr, slice) = self.memory_management.allocate(len + 1).unwrap();
-                for (src, dst) in bytes.chain(core::iter::once(0)).zip(slice.iter_mut()) {
-                    *dst = src;
-                }
-                addr.try_into().unwrap()
+    /// `size` is `None` if the memory wasn't allocated by this instance,
+    /// nothing is freed in that case.
+    unsafe fn free(&mut self, addr: u32, size: Option<usize>) {
+        if let (Some(size), Some(release)) = (size, self.release) {
+            if addr != 0 {
+                release(self.memory_management, addr.into(), size);
             }
-            None => 0,
         }
     }
 
@@ -363,6 +681,220 @@ impl<'a, 'b> Multiboot<'a, 'b> {
         }
     }
 
+    /// Check whether the memory bounds contradict the memory map.
+    ///
+    /// The bounds may be smaller than what [`memory_bounds`] computes from the map,
+    /// but larger values claim memory that isn't available.
+    /// If either the bounds or the map are missing, there is nothing to check.
+    ///
+    /// [`memory_bounds`]: fn.memory_bounds.html
+    pub fn check_memory_bounds(&'a self) -> Result<(), MemoryBoundsMismatch> {
+        let (lower, upper) = match (self.lower_memory_bound(), self.upper_memory_bound()) {
+            (Some(lower), Some(upper)) => (lower, upper),
+            _ => return Ok(()),
+        };
+        let memory_map = match self.memory_regions() {
+            Some(regions) => memory_bounds(regions),
+            None => return Ok(()),
+        };
+        if lower > memory_map.0 || upper > memory_map.1 {
+            Err(MemoryBoundsMismatch {
+                bounds: (lower, upper),
+                memory_map,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check whether the information is consistent and everything it refers to is mapped.
+    ///
+    /// A kernel shouldn't trust the bootloader: this finds pointers that
+    /// [`PhysicalMemoryMapper`] can't translate, strings that aren't terminated within
+    /// [`MAX_STRING_LENGTH`] bytes or aren't UTF-8, malformed memory maps, modules
+    /// that end before they start or overlap the information struct, framebuffer
+    /// pitches that are too small, lower memory above 640 KiB and both a.out and
+    /// ELF symbols. ELF sections aren't checked, they may have virtual addresses.
+    ///
+    /// [`PhysicalMemoryMapper`]: trait.PhysicalMemoryMapper.html
+    /// [`MAX_STRING_LENGTH`]: constant.MAX_STRING_LENGTH.html
+    pub fn validate(&'a self) -> ValidationReport {
+        use self::OccupiedRegionKind as Kind;
+        use self::ValidationIssue::*;
+        let mut report = ValidationReport::default();
+        let header = &self.header;
+        let mapped = |addr: PAddr, length: usize| unsafe {
+            self.memory_management
+                .paddr_to_slice(addr, length)
+                .is_some()
+        };
+        let check_mapped = |report: &mut ValidationReport, kind, addr: u32, length| {
+            if addr == 0 || !mapped(addr.into(), length) {
+                report.push(Unmapped(kind));
+            }
+        };
+
+        if self.has_memory_bounds() && header.mem_lower > 640 {
+            report.push(LowerMemoryTooLarge(header.mem_lower));
+        }
+        if self.has_cmdline() {
+            self.validate_string(&mut report, Kind::CommandLine, header.cmdline);
+        }
+        if self.has_boot_loader_name() {
+            self.validate_string(&mut report, Kind::BootLoaderName, header.boot_loader_name);
+        }
+        if self.has_modules() {
+            match self.module_table() {
+                Some(table) => {
+                    let info = self
+                        .mboot_ptr
+                        .map(|addr| addr..addr + size_of::<MultibootInfo>() as PAddr);
+                    for (index, module) in table.iter().enumerate() {
+                        let (start, end) = (PAddr::from(module.start), PAddr::from(module.end));
+                        if start > end {
+                            report.push(InvalidModuleRange(index));
+                        } else {
+                            if !mapped(start, (end - start) as usize) {
+                                report.push(Unmapped(Kind::Module(index)));
+                            }
+                            if let Some(ref info) = info {
+                                if start < info.end && info.start < end {
+                                    report.push(ModuleOverlapsInfo(index));
+                                }
+                            }
+                        }
+                        if module.string != 0 {
+                            self.validate_string(
+                                &mut report,
+                                Kind::ModuleString(index),
+                                module.string,
+                            );
+                        }
+                    }
+                }
+                None => report.push(Unmapped(Kind::ModuleTable)),
+            }
+        }
+        if self.has_aout_symbols() && self.has_elf_symbols() {
+            report.push(ConflictingSymbols);
+        }
+        // with conflicting symbols, both interpretations of the union are checked
+        if self.has_elf_symbols() {
+            let e = unsafe { self.header.symbols.elf };
+            match e.table_size() {
+                Some(length) => check_mapped(&mut report, Kind::ElfSectionTable, e.addr, length),
+                None => report.push(Unmapped(Kind::ElfSectionTable)),
+            }
+        }
+        if self.has_aout_symbols() {
+            let a = unsafe { self.header.symbols.aout };
+            match a.tables_size() {
+                Some(length) => check_mapped(&mut report, Kind::AOutSymbols, a.addr, length),
+                None => report.push(Unmapped(Kind::AOutSymbols)),
+            }
+        }
+        if self.has_memory_map() {
+            if mapped(header.mmap_addr.into(), header.mmap_length as usize) {
+                self.validate_memory_map(&mut report);
+            } else {
+                report.push(Unmapped(Kind::MemoryMap));
+            }
+        }
+        if self.has_drives() {
+            check_mapped(
+                &mut report,
+                Kind::Drives,
+                header.drives_addr,
+                header.drives_length as usize,
+            );
+        }
+        if self.has_apm_table() {
+            check_mapped(&mut report, Kind::ApmTable, header.apm_table, 20);
+        }
+        if self.has_vbe() {
+            check_mapped(
+                &mut report,
+                Kind::VbeControlInfo,
+                header.vbe_control_info,
+                512,
+            );
+            check_mapped(&mut report, Kind::VbeModeInfo, header.vbe_mode_info, 256);
+        }
+        if let Some(table) = self.framebuffer_table() {
+            if u64::from(table.pitch) < u64::from(table.width) * u64::from(table.bpp) / 8 {
+                report.push(FramebufferPitchTooSmall);
+            }
+            if let Some(ColorInfoType::Palette(palette)) = table.color_info() {
+                check_mapped(
+                    &mut report,
+                    Kind::Palette,
+                    palette.palette_addr,
+                    usize::from(palette.palette_num_colors) * 3,
+                );
+            }
+        }
+        report
+    }
+
+    /// Check that a string is mapped, terminated and valid UTF-8.
+    fn validate_string(&self, report: &mut ValidationReport, kind: OccupiedRegionKind, addr: u32) {
+        let addr = PAddr::from(addr);
+        if addr == 0 {
+            report.push(ValidationIssue::Unmapped(kind));
+            return;
+        }
+        let byte = |offset: usize| unsafe {
+            self.memory_management
+                .paddr_to_slice(addr + offset as PAddr, 1)
+                .map(|byte| byte[0])
+        };
+        let mut len = 0;
+        loop {
+            match byte(len) {
+                None => {
+                    report.push(ValidationIssue::Unmapped(kind));
+                    return;
+                }
+                Some(0) => break,
+                Some(_) if len == MAX_STRING_LENGTH => {
+                    report.push(ValidationIssue::UnterminatedString(kind));
+                    return;
+                }
+                Some(_) => len += 1,
+            }
+        }
+        let bytes = unsafe { self.memory_management.paddr_to_slice(addr, len) };
+        if bytes.is_some_and(|bytes| str::from_utf8(bytes).is_err()) {
+            report.push(ValidationIssue::InvalidUtf8(kind));
+        }
+    }
+
+    /// Check that the memory map consists of whole entries, which are large enough.
+    ///
+    /// The memory map has to be mapped.
+    fn validate_memory_map(&self, report: &mut ValidationReport) {
+        let start = PAddr::from(self.header.mmap_addr);
+        let end = start + PAddr::from(self.header.mmap_length);
+        let mut addr = start;
+        let mut index = 0;
+        while addr + size_of::<u32>() as PAddr <= end {
+            let size = unsafe {
+                self.memory_management
+                    .paddr_to_slice(addr, size_of::<u32>())
+            }
+            .map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+            if size < 20 {
+                report.push(ValidationIssue::MemoryEntryTooSmall(index));
+                return;
+            }
+            addr += PAddr::from(size) + size_of::<u32>() as PAddr;
+            index += 1;
+        }
+        if addr != end {
+            report.push(ValidationIssue::MemoryMapLength);
+        }
+    }
+
     /// Indicates which bios disk device the boot loader loaded the OS image from.
     ///
     /// If the OS image was not loaded from a bios disk, then this
@@ -377,6 +909,9 @@ impl<'a, 'b> Multiboot<'a, 'b> {
     }
 
     /// Command line passed to the kernel.
+    ///
+    /// Returns `None` if the command line isn't valid UTF-8,
+    /// use [`command_line_bytes`](#method.command_line_bytes) to access it anyway.
     pub fn command_line(&self) -> Option<&'a str> {
         if self.has_cmdline() {
             unsafe { self.convert_c_string(self.header.cmdline as PAddr) }
@@ -385,22 +920,19 @@ impl<'a, 'b> Multiboot<'a, 'b> {
         }
     }
 
-    /// Command line to be passed to the kernel.
-    ///
-    /// The given string will be copied to newly allocated memory.
-    pub fn set_command_line(&mut self, cmdline: Option<&str>) {
-        // free the old string if it exists
+    /// Command line passed to the kernel, without the terminating zero.
+    pub fn command_line_bytes(&self) -> Option<&'a [u8]> {
         if self.has_cmdline() {
-            unsafe {
-                self.memory_management
-                    .deallocate(self.header.cmdline.into())
-            };
+            unsafe { c_string_bytes(self.memory_management, self.header.cmdline.into()) }
+        } else {
+            None
         }
-        self.set_has_cmdline(cmdline.is_some());
-        self.header.cmdline = unsafe { self.convert_to_c_string(cmdline) };
     }
 
     /// Get the name of the bootloader.
+    ///
+    /// Returns `None` if the name isn't valid UTF-8,
+    /// use [`boot_loader_name_bytes`](#method.boot_loader_name_bytes) to access it anyway.
     pub fn boot_loader_name(&self) -> Option<&'a str> {
         if self.has_boot_loader_name() {
             unsafe { self.convert_c_string(self.header.boot_loader_name as PAddr) }
@@ -409,23 +941,23 @@ impl<'a, 'b> Multiboot<'a, 'b> {
         }
     }
 
-    /// Set the name of the bootloader.
-    ///
-    /// The given string will be copied to newly allocated memory.
-    pub fn set_boot_loader_name(&mut self, name: Option<&str>) {
-        // free the old string if it exists
+    /// Get the name of the bootloader, without the terminating zero.
+    pub fn boot_loader_name_bytes(&self) -> Option<&'a [u8]> {
         if self.has_boot_loader_name() {
-            unsafe {
-                self.memory_management
-                    .deallocate(self.header.boot_loader_name.into())
-            };
+            unsafe { c_string_bytes(self.memory_management, self.header.boot_loader_name.into()) }
+        } else {
+            None
         }
-        self.set_has_boot_loader_name(name.is_some());
-        self.header.boot_loader_name = unsafe { self.convert_to_c_string(name) };
     }
 
     /// Discover all additional modules in multiboot.
-    pub fn modules(&'a self) -> Option<ModuleIter<'a, 'b>> {
+    pub fn modules(&'a self) -> Option<ModuleIter<'a, 'b, M>> {
+        self.module_table()
+            .map(|mods| ModuleIter { mb: self, mods })
+    }
+
+    /// Get the raw module table, if it exists.
+    fn module_table(&self) -> Option<&'a [MBModule]> {
         if self.has_modules() {
             unsafe {
                 self.memory_management
@@ -434,9 +966,8 @@ impl<'a, 'b> Multiboot<'a, 'b> {
                         self.header.mods_count as usize * size_of::<MBModule>(),
                     )
                     .map(|slice| {
-                        let ptr = transmute(slice.as_ptr());
-                        let mods = slice::from_raw_parts(ptr, self.header.mods_count as usize);
-                        ModuleIter { mb: self, mods }
+                        let ptr = transmute::<*const u8, *const MBModule>(slice.as_ptr());
+                        slice::from_raw_parts(ptr, self.header.mods_count as usize)
                     })
             }
         } else {
@@ -444,55 +975,47 @@ impl<'a, 'b> Multiboot<'a, 'b> {
         }
     }
 
-    /// Publish modules to the kernel.
+    /// Find the first module with the given name.
     ///
-    /// This copies the given metadata into newly allocated memory.
+    /// See [`Module::has_name`] for how names are matched.
     ///
-    /// Note that the addresses in each [`Module`] must be and stay valid.
+    /// [`Module::has_name`]: struct.Module.html#method.has_name
+    pub fn module_by_name(&'a self, name: &str) -> Option<Module<'a>> {
+        self.modules()?.find(|module| module.has_name(name))
+    }
+
+    /// Get the contents of a module.
     ///
-    /// [`Module`]: struct.Module.html
-    pub fn set_modules(&mut self, modules: Option<&[Module]>) {
-        // free the existing modules
-        if self.has_modules() {
-            unsafe {
-                if let Some(mods) = self.memory_management.paddr_to_slice(
-                    self.header.mods_addr.into(),
-                    self.header.mods_count as usize * core::mem::size_of::<MBModule>(),
-                ) {
-                    let mods = slice::from_raw_parts(
-                        mods.as_ptr().cast::<MBModule>(),
-                        self.header.mods_count as usize,
-                    );
-                    for module in mods {
-                        self.memory_management.deallocate(module.string.into());
-                    }
-                    self.memory_management
-                        .deallocate(self.header.mods_addr.into());
-                }
-            }
+    /// This is [`Module::data`] with the memory management of this struct.
+    /// Returns `None` if the module isn't in the module table.
+    ///
+    /// [`Module::data`]: struct.Module.html#method.data
+    pub fn module_data(&self, module: &Module) -> Option<&'static [u8]> {
+        if !self.is_loaded_module(module) {
+            return None;
         }
-        self.set_has_modules(modules.is_some());
-        if let Some(mods) = modules {
-            let len = mods.len();
-            self.header.mods_count = mods.len().try_into().unwrap();
-            self.header.mods_addr = unsafe {
-                let (addr, slice) = self
-                    .memory_management
-                    .allocate(len * core::mem::size_of::<MBModule>())
-                    .unwrap();
-                // change type
-                let slice = slice::from_raw_parts_mut(slice.as_mut_ptr().cast::<MBModule>(), len);
-                for (src, dst) in mods.iter().zip(slice.iter_mut()) {
-                    *dst = MBModule {
-                        start: src.start.try_into().unwrap(),
-                        end: src.end.try_into().unwrap(),
-                        string: self.convert_to_c_string(src.string),
-                        reserved: 0,
-                    }
-                }
-                addr.try_into().unwrap()
-            };
+        unsafe { module.data(self.memory_management) }
+    }
+
+    /// Find out what a module contains.
+    ///
+    /// This is [`Module::detect_kind`] with the memory management of this struct.
+    /// Returns [`ModuleKind::Unknown`] if the module isn't in the module table.
+    ///
+    /// [`Module::detect_kind`]: struct.Module.html#method.detect_kind
+    /// [`ModuleKind::Unknown`]: ../module_kind/enum.ModuleKind.html#variant.Unknown
+    pub fn module_kind(&self, module: &Module) -> ModuleKind {
+        if !self.is_loaded_module(module) {
+            return ModuleKind::Unknown;
         }
+        unsafe { module.detect_kind(self.memory_management) }
+    }
+
+    /// Check whether the module table contains a module with the same location.
+    fn is_loaded_module(&self, module: &Module) -> bool {
+        self.modules().is_some_and(|mut modules| {
+            modules.any(|m| m.start == module.start && m.end == module.end)
+        })
     }
 
     /// Get the symbols.
@@ -536,7 +1059,7 @@ impl<'a, 'b> Multiboot<'a, 'b> {
     }
 
     /// Discover all memory regions in the multiboot memory map.
-    pub fn memory_regions(&'a self) -> Option<MemoryMapIter<'a, 'b>> {
+    pub fn memory_regions(&'a self) -> Option<MemoryMapIter<'a, 'b, M>> {
         match self.has_memory_map() {
             true => {
                 let start = self.header.mmap_addr;
@@ -559,51 +1082,263 @@ impl<'a, 'b> Multiboot<'a, 'b> {
     ///
     /// [`MemoryEntry`]: struct.MemoryEntry.html
     pub fn set_memory_regions(&mut self, regions: Option<(PAddr, usize)>) {
-        self.set_has_memory_map(regions.is_some());
-        if let Some((addr, count)) = regions {
-            self.header.mmap_addr = addr.try_into().unwrap();
-            self.header.mmap_length = (count * core::mem::size_of::<MemoryEntry>())
-                .try_into()
-                .unwrap();
-        }
+        self.try_set_memory_regions(regions).unwrap()
     }
 
-    /// Return end address of multiboot image.
+    /// Publish the memory regions to the kernel.
     ///
-    /// This function can be used to figure out a (hopefully) safe offset
-    /// in the first region of memory to start using as free memory.
-    pub fn find_highest_address(&self) -> PAddr {
-        let end = cmp::max(
-            self.header.cmdline as u64 + self.command_line().map_or(0, |f| f.len()) as u64,
-            self.header.boot_loader_name as u64
-                + self.boot_loader_name().map_or(0, |f| f.len()) as u64,
-        )
-        .max(match self.symbols() {
-            Some(SymbolType::Elf(e)) => (e.addr + e.num * e.size) as u64,
-            Some(SymbolType::AOut(a)) => {
-                (a.addr + a.tabsize + a.strsize + 2 * core::mem::size_of::<u32>() as u32) as u64
-            }
-            None => 0,
-        })
-        .max((self.header.mmap_addr + self.header.mmap_length) as u64)
-        .max((self.header.drives_addr + self.header.drives_length) as u64)
-        .max(
-            self.header.mods_addr as u64
-                + self.header.mods_count as u64 * core::mem::size_of::<MBModule>() as u64,
-        )
-        .max(
-            self.modules()
-                .into_iter()
-                .flatten()
-                .map(|m| m.end)
-                .max()
-                .unwrap_or(0),
-        );
+    /// Like [`set_memory_regions`], but the information is left unchanged
+    /// if the address or the length don't fit into 32 bits.
+    ///
+    /// [`set_memory_regions`]: #method.set_memory_regions
+    pub fn try_set_memory_regions(
+        &mut self,
+        regions: Option<(PAddr, usize)>,
+    ) -> Result<(), SetError> {
+        self.try_set_raw_memory_map(regions, size_of::<MemoryEntry>())
+    }
 
-        round_up!(end, 4096)
+    /// Publish memory regions with extended attributes to the kernel.
+    ///
+    /// The parameter is a pair of address and number of [`ExtendedMemoryEntry`]s.
+    ///
+    /// Note that the underlying memory has to stay intact.
+    ///
+    /// [`ExtendedMemoryEntry`]: struct.ExtendedMemoryEntry.html
+    pub fn set_extended_memory_regions(&mut self, regions: Option<(PAddr, usize)>) {
+        self.try_set_extended_memory_regions(regions).unwrap()
     }
 
-    /// Return the framebuffer table, if it exists.
+    /// Publish memory regions with extended attributes to the kernel.
+    ///
+    /// Like [`set_extended_memory_regions`], but the information is left unchanged
+    /// if the address or the length don't fit into 32 bits.
+    ///
+    /// [`set_extended_memory_regions`]: #method.set_extended_memory_regions
+    pub fn try_set_extended_memory_regions(
+        &mut self,
+        regions: Option<(PAddr, usize)>,
+    ) -> Result<(), SetError> {
+        self.try_set_raw_memory_map(regions, size_of::<ExtendedMemoryEntry>())
+    }
+
+    /// Publish a memory map that is already in memory to the kernel.
+    ///
+    /// The parameter is a pair of address and number of entries,
+    /// each entry is `stride` bytes long including the size field.
+    /// The information is left unchanged if the address or the length
+    /// don't fit into 32 bits.
+    ///
+    /// Note that the underlying memory has to stay intact.
+    pub fn try_set_raw_memory_map(
+        &mut self,
+        regions: Option<(PAddr, usize)>,
+        stride: usize,
+    ) -> Result<(), SetError> {
+        if let Some((addr, count)) = regions {
+            let addr = addr.try_into().map_err(|_| SetError::AddressTooLarge)?;
+            let length = count
+                .checked_mul(stride)
+                .and_then(|length| length.try_into().ok())
+                .ok_or(SetError::TooManyEntries)?;
+            if PAddr::from(addr) != PAddr::from(self.header.mmap_addr) {
+                self.free_memory_map();
+            }
+            self.header.mmap_addr = addr;
+            self.header.mmap_length = length;
+        } else {
+            self.free_memory_map();
+        }
+        self.set_has_memory_map(regions.is_some());
+        Ok(())
+    }
+
+    /// Free the current memory map if it was allocated by a setter.
+    fn free_memory_map(&mut self) {
+        let size = self.owned.memory_map.take();
+        unsafe { self.free(self.header.mmap_addr, size) };
+    }
+
+    /// Discover all memory that is available and not used by the boot information.
+    ///
+    /// This takes the available regions of the memory map and removes every range
+    /// returned by [`occupied_regions`] as well as the ranges given in `reserved`,
+    /// e.g. the kernel image. Regions that are marked as not available are removed, too,
+    /// in case they overlap with available ones.
+    ///
+    /// The resulting ranges are aligned inward to 4 KiB pages.
+    ///
+    /// [`occupied_regions`]: struct.Multiboot.html#method.occupied_regions
+    pub fn free_memory_regions<'r>(
+        &'a self,
+        reserved: &'r [Range<PAddr>],
+    ) -> Option<FreeMemoryIter<'a, 'b, 'r, M>> {
+        self.memory_regions().map(|regions| {
+            let mut iter = FreeMemoryIter {
+                mb: self,
+                regions,
+                reserved,
+                used: UsedRanges::default(),
+                current: 0..0,
+            };
+            iter.collect_used();
+            iter
+        })
+    }
+
+    /// Discover the physical memory occupied by this information.
+    ///
+    /// This yields the information struct itself (if it was created with [`from_ptr`])
+    /// and everything it refers to: strings, the module table, the modules,
+    /// the memory map, the drives, the symbols (including ELF sections the bootloader
+    /// copied into memory), the APM and VBE tables and the framebuffer palette.
+    ///
+    /// This is useful to know which memory can be reclaimed after copying the
+    /// information somewhere else.
+    ///
+    /// [`from_ptr`]: struct.Multiboot.html#method.from_ptr
+    pub fn occupied_regions(&'a self) -> OccupiedRegionIter<'a, 'b, M> {
+        use self::OccupiedRegionKind::*;
+        let header = &self.header;
+        let region = |kind, range| Some(OccupiedRegion { kind, range });
+        let string = |kind, addr: u32| {
+            unsafe { self.c_string_range(addr.into()) }.map(|range| OccupiedRegion { kind, range })
+        };
+        let mods = self.module_table().unwrap_or(&[]);
+        let mut fixed: [Option<OccupiedRegion>; 11] = Default::default();
+        fixed[0] = self
+            .mboot_ptr
+            .and_then(|addr| region(Info, addr..addr + size_of::<MultibootInfo>() as PAddr));
+        if self.has_cmdline() {
+            fixed[1] = string(CommandLine, header.cmdline);
+        }
+        if self.has_boot_loader_name() {
+            fixed[2] = string(BootLoaderName, header.boot_loader_name);
+        }
+        if self.has_modules() {
+            fixed[3] = region(
+                ModuleTable,
+                phys_range(header.mods_addr, size_of_val(mods) as PAddr),
+            );
+        }
+        if self.has_memory_map() {
+            fixed[4] = region(
+                MemoryMap,
+                phys_range(header.mmap_addr, header.mmap_length.into()),
+            );
+        }
+        if self.has_drives() {
+            fixed[5] = region(
+                Drives,
+                phys_range(header.drives_addr, header.drives_length.into()),
+            );
+        }
+        fixed[6] = match self.symbols() {
+            Some(SymbolType::Elf(e)) => region(
+                ElfSectionTable,
+                phys_range(e.addr, PAddr::from(e.num) * PAddr::from(e.size)),
+            ),
+            Some(SymbolType::AOut(a)) => region(
+                AOutSymbols,
+                phys_range(
+                    a.addr,
+                    PAddr::from(a.tabsize) + PAddr::from(a.strsize) + 2 * size_of::<u32>() as PAddr,
+                ),
+            ),
+            None => None,
+        };
+        if self.has_apm_table() {
+            fixed[7] = region(ApmTable, phys_range(header.apm_table, 20));
+        }
+        if self.has_vbe() {
+            fixed[8] = region(VbeControlInfo, phys_range(header.vbe_control_info, 512));
+            fixed[9] = region(VbeModeInfo, phys_range(header.vbe_mode_info, 256));
+        }
+        if let Some(ColorInfoType::Palette(palette)) =
+            self.framebuffer_table().and_then(|t| t.color_info())
+        {
+            fixed[10] = region(
+                Palette,
+                phys_range(
+                    palette.palette_addr,
+                    PAddr::from(palette.palette_num_colors) * 3,
+                ),
+            );
+        }
+        OccupiedRegionIter {
+            mb: self,
+            fixed,
+            fixed_index: 0,
+            mods,
+            module_index: 0,
+            module_string: None,
+            section_index: 0,
+        }
+    }
+
+    /// Get the physical range of a section that the bootloader copied into memory.
+    ///
+    /// Sections that are part of the loaded image (`SHF_ALLOC`) are skipped, their
+    /// address is the one from the ELF file and may be virtual.
+    fn elf_section_range(&self, symbols: &ElfSymbols, index: u32) -> Option<Range<PAddr>> {
+        const SHT_NULL: u32 = 0;
+        const SHT_NOBITS: u32 = 8;
+        const SHF_ALLOC: u64 = 0x2;
+        let addr = PAddr::from(symbols.addr) + PAddr::from(index) * PAddr::from(symbols.size);
+        let header = unsafe {
+            self.memory_management
+                .paddr_to_slice(addr, symbols.size as usize)?
+        };
+        let u32_at = |offset: usize| {
+            header
+                .get(offset..offset + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        };
+        let u64_at = |offset: usize| {
+            header
+                .get(offset..offset + 8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        };
+        let (ty, flags, start, size) = match symbols.size {
+            // Elf64_Shdr
+            64 => (u32_at(4)?, u64_at(8)?, u64_at(24)?, u64_at(32)?),
+            // Elf32_Shdr
+            40 => (
+                u32_at(4)?,
+                u32_at(8)?.into(),
+                u32_at(12)?.into(),
+                u32_at(20)?.into(),
+            ),
+            _ => return None,
+        };
+        if ty == SHT_NULL || ty == SHT_NOBITS || flags & SHF_ALLOC != 0 || start == 0 {
+            return None;
+        }
+        Some(start..start.saturating_add(size))
+    }
+
+    /// Return end address of multiboot image.
+    ///
+    /// This is the end of the highest region returned by [`occupied_regions`],
+    /// rounded up to 4 KiB. The information struct itself is not considered,
+    /// its address is already known to the caller.
+    ///
+    /// This function can be used to figure out a (hopefully) safe offset
+    /// in the first region of memory to start using as free memory.
+    ///
+    /// [`occupied_regions`]: struct.Multiboot.html#method.occupied_regions
+    pub fn find_highest_address(&self) -> PAddr {
+        let end = self
+            .occupied_regions()
+            .filter(|region| region.kind != OccupiedRegionKind::Info)
+            .map(|region| region.range.end)
+            .max()
+            .unwrap_or(0);
+
+        round_up!(end, PAGE_SIZE)
+    }
+
+    /// Return the framebuffer table, if it exists.
     pub fn framebuffer_table(&self) -> Option<&FramebufferTable> {
         if self.has_framebuffer_table() {
             Some(&self.header.framebuffer_table)
@@ -620,6 +1355,790 @@ impl<'a, 'b> Multiboot<'a, 'b> {
             None => FramebufferTable::default(),
         };
     }
+
+    /// Copy this information and everything it refers to into `buffer`,
+    /// which is located at `new_base`.
+    ///
+    /// The copy is contiguous and all its pointers are relative to `new_base`,
+    /// so it can be read with [`from_ptr`] using memory management that maps
+    /// `new_base` to `buffer`. A 32-bit kernel in the higher half can pass
+    /// a virtual address here, other kernels should pass the physical address
+    /// and translate it in [`PhysicalMemoryMapper`].
+    ///
+    /// Strings, the module table, the memory map, the drives, the ELF section
+    /// headers or a.out symbol tables, the APM table, the VBE tables and the palette
+    /// are copied. The modules, the ELF sections and the ROM configuration table
+    /// are not, the copy keeps referring to the originals. [`clone_references`]
+    /// returns the memory that still has to be preserved.
+    ///
+    /// Returns the number of bytes written, which is the same as [`clone_size`],
+    /// or `None` if the buffer is too small, doesn't fit below 4 GiB or
+    /// something can't be read.
+    ///
+    /// [`from_ptr`]: #method.from_ptr
+    /// [`PhysicalMemoryMapper`]: trait.PhysicalMemoryMapper.html
+    /// [`clone_size`]: #method.clone_size
+    /// [`clone_references`]: #method.clone_references
+    pub fn clone_into(&'a self, buffer: &mut [u8], new_base: PAddr) -> Option<usize> {
+        self.clone_builder()?.build(buffer, new_base)
+    }
+
+    /// Get the size of the buffer that [`clone_into`](#method.clone_into) needs.
+    ///
+    /// Returns `None` if something can't be read.
+    pub fn clone_size(&'a self) -> Option<usize> {
+        self.clone_builder().map(|builder| builder.size())
+    }
+
+    /// Discover the memory a copy made by [`clone_into`] still refers to.
+    ///
+    /// These are the modules and the ELF sections the bootloader copied into
+    /// memory (like the symbol and string tables), they must be preserved as
+    /// long as the copy is used.
+    ///
+    /// [`clone_into`]: #method.clone_into
+    pub fn clone_references(&'a self) -> impl Iterator<Item = OccupiedRegion> + 'a {
+        self.occupied_regions().filter(|region| {
+            matches!(
+                region.kind,
+                OccupiedRegionKind::Module(_) | OccupiedRegionKind::ElfSection(_)
+            )
+        })
+    }
+
+    /// Get a builder that writes a copy of this information.
+    fn clone_builder(&'a self) -> Option<MultibootInfoBuilder<'a>> {
+        let memory_management: &'a dyn PhysicalMemoryMapper = &self.memory_management;
+        let header = &self.header;
+        let bytes = |addr: u32, length: usize| unsafe {
+            memory_management.paddr_to_slice(addr.into(), length)
+        };
+        let string = |addr: u32| unsafe { c_string_bytes(memory_management, addr.into()) };
+        let mut builder = MultibootInfoBuilder::new();
+        if self.has_memory_bounds() {
+            builder.memory_bounds = Some((header.mem_lower, header.mem_upper));
+        }
+        builder.boot_device = self.boot_device();
+        if self.has_cmdline() {
+            builder.command_line = Some(string(header.cmdline)?);
+        }
+        if self.has_boot_loader_name() {
+            builder.boot_loader_name = Some(string(header.boot_loader_name)?);
+        }
+        if self.has_modules() {
+            let table = self.module_table()?;
+            builder.modules = Some(BuilderModules::Table(table, memory_management));
+        }
+        builder.symbols = match self.symbols() {
+            Some(SymbolType::Elf(e)) => Some(BuilderSymbols::Elf {
+                headers: bytes(e.addr, e.table_size()?)?,
+                num: e.num,
+                size: e.size,
+                shndx: e.shndx,
+            }),
+            Some(SymbolType::AOut(a)) => Some(BuilderSymbols::AOut {
+                tables: bytes(a.addr, a.tables_size()?)?,
+                tabsize: a.tabsize,
+                strsize: a.strsize,
+            }),
+            None => None,
+        };
+        if self.has_memory_map() {
+            builder.memory_map = Some(bytes(header.mmap_addr, header.mmap_length as usize)?);
+        }
+        if self.has_drives() {
+            builder.drives = Some(bytes(header.drives_addr, header.drives_length as usize)?);
+        }
+        if self.has_config_table() {
+            builder.config_table = Some(header._config_table);
+        }
+        if self.has_apm_table() {
+            builder.apm_table = Some(bytes(header.apm_table, 20)?);
+        }
+        if self.has_vbe() {
+            builder.vbe = Some(BuilderVbe {
+                control_info: bytes(header.vbe_control_info, 512)?,
+                mode_info: bytes(header.vbe_mode_info, 256)?,
+                mode: header._vbe_mode,
+                interface: (
+                    header._vbe_interface_seg,
+                    header._vbe_interface_off,
+                    header._vbe_interface_len,
+                ),
+            });
+        }
+        if let Some(table) = self.framebuffer_table() {
+            if let Some(ColorInfoType::Palette(palette)) = table.color_info() {
+                builder.palette = Some(bytes(
+                    palette.palette_addr,
+                    usize::from(palette.palette_num_colors) * 3,
+                )?);
+            }
+            builder.framebuffer_table = Some(table.clone());
+        }
+        Some(builder)
+    }
+}
+
+/// Setting fields that point to newly allocated memory.
+impl<'a, 'b, M: MemoryManagement + ?Sized> Multiboot<'a, 'b, M> {
+    /// Allocate memory whose address fits into 32 bits.
+    unsafe fn try_allocate(
+        &mut self,
+        request: AllocationRequest,
+    ) -> Result<(u32, &mut [u8]), SetError> {
+        let (addr, buffer) = self
+            .memory_management
+            .allocate_request(request)
+            .ok_or(SetError::AllocationFailed)?;
+        let buffer: *mut [u8] = buffer;
+        match addr.try_into() {
+            Ok(addr32) => {
+                self.release = Some(M::deallocate_sized);
+                Ok((addr32, &mut *buffer))
+            }
+            Err(_) => {
+                self.memory_management.deallocate_sized(addr, request.size);
+                Err(SetError::AddressTooLarge)
+            }
+        }
+    }
+
+    /// Copy a string into newly allocated memory and terminate it.
+    ///
+    /// Strings that contain a zero byte are rejected, freeing them again
+    /// relies on their length.
+    /// This unsafe block requires the possibility to allocate memory.
+    unsafe fn try_convert_to_c_string(&mut self, string: Option<&[u8]>) -> Result<u32, SetError> {
+        match string {
+            Some(s) if s.contains(&0) => Err(SetError::InteriorNul),
+            Some(s) => {
+                let request = AllocationRequest::new(s.len() + 1, AllocationPurpose::String);
+                let (addr, slice) = self.try_allocate(request)?;
+                slice[..s.len()].copy_from_slice(s);
+                slice[s.len()] = 0;
+                Ok(addr)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Command line to be passed to the kernel.
+    ///
+    /// The given string will be copied to newly allocated memory.
+    /// Panics on failure, see [`try_set_command_line`](#method.try_set_command_line).
+    pub fn set_command_line(&mut self, cmdline: Option<&str>) {
+        self.try_set_command_line(cmdline).unwrap()
+    }
+
+    /// Command line to be passed to the kernel.
+    ///
+    /// The given string will be copied to newly allocated memory.
+    /// On failure, the information is left unchanged.
+    pub fn try_set_command_line(&mut self, cmdline: Option<&str>) -> Result<(), SetError> {
+        let addr = unsafe { self.try_convert_to_c_string(cmdline.map(str::as_bytes))? };
+        // free the old string if it was allocated here
+        let old = self.owned.cmdline.take();
+        unsafe { self.free(self.header.cmdline, old) };
+        self.owned.cmdline = cmdline.map(|s| s.len() + 1);
+        self.set_has_cmdline(cmdline.is_some());
+        self.header.cmdline = addr;
+        Ok(())
+    }
+
+    /// Set the name of the bootloader.
+    ///
+    /// The given string will be copied to newly allocated memory.
+    /// Panics on failure, see [`try_set_boot_loader_name`](#method.try_set_boot_loader_name).
+    pub fn set_boot_loader_name(&mut self, name: Option<&str>) {
+        self.try_set_boot_loader_name(name).unwrap()
+    }
+
+    /// Set the name of the bootloader.
+    ///
+    /// The given string will be copied to newly allocated memory.
+    /// On failure, the information is left unchanged.
+    pub fn try_set_boot_loader_name(&mut self, name: Option<&str>) -> Result<(), SetError> {
+        let addr = unsafe { self.try_convert_to_c_string(name.map(str::as_bytes))? };
+        // free the old string if it was allocated here
+        let old = self.owned.boot_loader_name.take();
+        unsafe { self.free(self.header.boot_loader_name, old) };
+        self.owned.boot_loader_name = name.map(|s| s.len() + 1);
+        self.set_has_boot_loader_name(name.is_some());
+        self.header.boot_loader_name = addr;
+        Ok(())
+    }
+
+    /// Decompress the module at `index` and update the module table.
+    ///
+    /// The module table then points to the decompressed copy,
+    /// which is placed in newly allocated memory. The compressed data is left alone.
+    /// The table itself is copied as well, and so are its strings if it was passed in.
+    ///
+    /// Fails with [`DecompressError::AllocationFailed`] if the copy or the table
+    /// can't be allocated below 4 GiB, everything is freed again in that case.
+    /// See [`decompress_module`] for details.
+    ///
+    /// [`DecompressError::AllocationFailed`]: ../decompress/enum.DecompressError.html#variant.AllocationFailed
+    ///
+    /// [`decompress_module`]: ../decompress/fn.decompress_module.html
+    #[cfg(feature = "decompress")]
+    pub fn decompress_module(&mut self, index: usize) -> Result<Module<'a>, DecompressError> {
+        let count = self.module_table().map_or(0, |table| table.len());
+        let module = match self.module_table().and_then(|table| table.get(index)) {
+            Some(entry) => unsafe { self.convert_module(entry) },
+            None => return Err(DecompressError::Inaccessible),
+        };
+        // the module comes from the table
+        let decompressed =
+            unsafe { decompress::decompress_module(&module, self.memory_management)? };
+        let size = (decompressed.end - decompressed.start) as usize;
+        let fits = |addr: PAddr| addr.try_into().ok();
+        let table = match (fits(decompressed.start), fits(decompressed.end)) {
+            (Some(start), Some(end)) => unsafe {
+                self.try_copy_modules(count, Some)
+                    .ok()
+                    .map(|table| (table, start, end))
+            },
+            _ => None,
+        };
+        let (table, start, end) = match table {
+            Some(table) => table,
+            None => {
+                unsafe {
+                    self.memory_management
+                        .deallocate_sized(decompressed.start, size)
+                };
+                return Err(DecompressError::AllocationFailed);
+            }
+        };
+        table.entries[index].start = start;
+        table.entries[index].end = end;
+        unsafe { self.replace_module_table(table, count) };
+        Ok(decompressed)
+    }
+
+    /// Publish modules to the kernel.
+    ///
+    /// This copies the given metadata into newly allocated memory.
+    /// Panics on failure, see [`try_set_modules`](#method.try_set_modules).
+    ///
+    /// Note that the addresses in each [`Module`] must be and stay valid.
+    ///
+    /// [`Module`]: struct.Module.html
+    pub fn set_modules(&mut self, modules: Option<&[Module]>) {
+        self.try_set_modules(modules).unwrap()
+    }
+
+    /// Publish modules to the kernel.
+    ///
+    /// This copies the given metadata into newly allocated memory.
+    /// On failure, the information is left unchanged.
+    ///
+    /// Note that the addresses in each [`Module`] must be and stay valid.
+    ///
+    /// [`Module`]: struct.Module.html
+    pub fn try_set_modules(&mut self, modules: Option<&[Module]>) -> Result<(), SetError> {
+        let table = unsafe { self.try_copy_module_table(modules.unwrap_or(&[]))? };
+        // free the strings of the existing modules, replacing the table frees it
+        for index in 0..self.module_table().map_or(0, |table| table.len()) {
+            unsafe { self.free_module_string(index) };
+        }
+        let count = table.entries.len();
+        unsafe { self.replace_module_table(table, count) };
+        Ok(())
+    }
+
+    /// Copy the modules and their strings into newly allocated memory.
+    ///
+    /// Everything is freed again on failure.
+    unsafe fn try_copy_module_table(
+        &mut self,
+        mods: &[Module],
+    ) -> Result<NewModuleTable<'a>, SetError> {
+        let fits = |addr: PAddr| addr <= PAddr::from(u32::MAX);
+        if !mods.iter().all(|m| fits(m.start) && fits(m.end)) {
+            return Err(SetError::AddressTooLarge);
+        }
+        let table = self.allocate_module_table(mods.len(), false)?;
+        for (index, module) in mods.iter().enumerate() {
+            let string = match self.try_convert_to_c_string(module.string_bytes()) {
+                Ok(string) => string,
+                Err(e) => {
+                    for entry in &table.entries[..index] {
+                        self.free_string(entry.string);
+                    }
+                    self.free(table.addr, Some(table.owned().size()));
+                    return Err(e);
+                }
+            };
+            table.entries[index] = MBModule {
+                start: module.start as u32,
+                end: module.end as u32,
+                string,
+                reserved: 0,
+            };
+        }
+        Ok(table)
+    }
+
+    /// Load modules for the kernel described by `header`.
+    ///
+    /// The contents of each module are copied into newly allocated memory,
+    /// which is page aligned if the kernel wants that. The module table is
+    /// then replaced by the loaded modules, see [`set_modules`].
+    ///
+    /// `kernel` is the memory occupied by the kernel, the modules must not
+    /// overlap it (see [`check_modules`]). On failure, the memory allocated
+    /// for the modules is freed and there are no modules.
+    ///
+    /// [`set_modules`]: #method.set_modules
+    /// [`check_modules`]: ../loader/fn.check_modules.html
+    pub fn load_modules(
+        &mut self,
+        header: &Header,
+        kernel: Range<PAddr>,
+        modules: &[ModuleSource],
+    ) -> Result<(), LoadError> {
+        let page_aligned = header.wants_modules_page_aligned();
+        let align = if page_aligned { MODULE_ALIGNMENT } else { 1 };
+        self.set_modules(None);
+        let result = modules.iter().enumerate().try_for_each(|(index, source)| {
+            let module = loader::copy_module(index, source, align, self.memory_management)?;
+            self.try_push_module(module).map_err(|e| {
+                let size = (module.end - module.start) as usize;
+                unsafe { self.memory_management.deallocate_sized(module.start, size) };
+                match e {
+                    SetError::AddressTooLarge => LoadError::AddressTooLarge(index),
+                    _ => LoadError::AllocationFailed(index),
+                }
+            })
+        });
+        let result = result.and_then(|()| {
+            let loaded = self.modules().into_iter().flatten();
+            loader::check_modules(loaded, kernel, page_aligned)
+        });
+        if result.is_err() {
+            for module in self.module_table().unwrap_or(&[]) {
+                let size = module.end.saturating_sub(module.start) as usize;
+                unsafe {
+                    self.memory_management
+                        .deallocate_sized(module.start.into(), size)
+                };
+            }
+            self.set_modules(None);
+        }
+        result
+    }
+
+    /// Append a module to the module table.
+    ///
+    /// See [`insert_module`](#method.insert_module).
+    pub fn push_module(&mut self, module: Module) {
+        self.try_push_module(module).unwrap()
+    }
+
+    /// Append a module to the module table.
+    ///
+    /// See [`try_insert_module`](#method.try_insert_module).
+    pub fn try_push_module(&mut self, module: Module) -> Result<(), SetError> {
+        let count = self.module_table().map_or(0, |table| table.len());
+        self.try_insert_module(count, module)
+    }
+
+    /// Insert a module into the module table at `index`.
+    ///
+    /// The table is copied to newly allocated memory, the string of the new
+    /// module as well. The strings of the existing modules are kept.
+    /// Panics on failure, see [`try_insert_module`](#method.try_insert_module).
+    ///
+    /// # Panics
+    /// Panics if `index` is larger than the number of modules.
+    pub fn insert_module(&mut self, index: usize, module: Module) {
+        self.try_insert_module(index, module).unwrap()
+    }
+
+    /// Insert a module into the module table at `index`.
+    ///
+    /// The table is copied to newly allocated memory, the string of the new
+    /// module as well. The strings of the existing modules are kept.
+    /// On failure, the information is left unchanged.
+    ///
+    /// # Panics
+    /// Panics if `index` is larger than the number of modules.
+    pub fn try_insert_module(&mut self, index: usize, module: Module) -> Result<(), SetError> {
+        let count = self.module_table().map_or(0, |table| table.len());
+        assert!(index <= count, "module index out of bounds");
+        let to_u32 = |addr: PAddr| addr.try_into().map_err(|_| SetError::AddressTooLarge);
+        let (start, end) = (to_u32(module.start)?, to_u32(module.end)?);
+        unsafe {
+            let string = self.try_convert_to_c_string(module.string_bytes())?;
+            let place = |old| Some(if old < index { old } else { old + 1 });
+            let table = match self.try_copy_modules(count + 1, place) {
+                Ok(table) => table,
+                Err(e) => {
+                    self.free_string(string);
+                    return Err(e);
+                }
+            };
+            table.entries[index] = MBModule {
+                start,
+                end,
+                string,
+                reserved: 0,
+            };
+            self.replace_module_table(table, count + 1);
+        }
+        Ok(())
+    }
+
+    /// Remove the module at `index` from the module table.
+    ///
+    /// The table is copied to newly allocated memory and the string of the
+    /// removed module is freed. The module itself is left alone,
+    /// its memory is returned so it can be reused.
+    /// If the last module is removed, there is no module table anymore.
+    /// Panics on failure, see [`try_remove_module`](#method.try_remove_module).
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn remove_module(&mut self, index: usize) -> Range<PAddr> {
+        self.try_remove_module(index).unwrap()
+    }
+
+    /// Remove the module at `index` from the module table.
+    ///
+    /// The table is copied to newly allocated memory and the string of the
+    /// removed module is freed. The module itself is left alone,
+    /// its memory is returned so it can be reused.
+    /// If the last module is removed, there is no module table anymore.
+    /// On failure, the information is left unchanged.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn try_remove_module(&mut self, index: usize) -> Result<Range<PAddr>, SetError> {
+        let count = self.module_table().map_or(0, |table| table.len());
+        assert!(index < count, "module index out of bounds");
+        unsafe {
+            let removed = self.module_table().unwrap_or(&[])[index];
+            let place = |old: usize| match old.cmp(&index) {
+                Ordering::Less => Some(old),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(old - 1),
+            };
+            let table = self.try_copy_modules(count - 1, place)?;
+            self.free_module_string(index);
+            self.replace_module_table(table, count - 1);
+            Ok(removed.start.into()..removed.end.into())
+        }
+    }
+
+    /// Only keep the modules for which `keep` returns true.
+    ///
+    /// `keep` is called exactly once for each module, in order.
+    /// The strings of the removed modules are freed.
+    /// If no module is kept, there is no module table anymore.
+    /// Panics on failure, see [`try_retain_modules`](#method.try_retain_modules).
+    pub fn retain_modules<F>(&mut self, keep: F)
+    where
+        F: FnMut(&Module<'a>) -> bool,
+    {
+        self.try_retain_modules(keep).unwrap()
+    }
+
+    /// Only keep the modules for which `keep` returns true.
+    ///
+    /// `keep` is called exactly once for each module, in order.
+    /// The strings of the removed modules are freed.
+    /// If no module is kept, there is no module table anymore.
+    /// On failure, `keep` isn't called and the information is left unchanged.
+    pub fn try_retain_modules<F>(&mut self, mut keep: F) -> Result<(), SetError>
+    where
+        F: FnMut(&Module<'a>) -> bool,
+    {
+        if !self.has_modules() {
+            return Ok(());
+        }
+        unsafe {
+            let old = self.module_table().unwrap_or(&[]);
+            // the table may end up being too large, but we can't call keep twice
+            let mut table = self.try_copy_modules(old.len(), Some)?;
+            let mut count = 0;
+            for (index, entry) in old.iter().enumerate() {
+                let foreign = table.is_foreign(index);
+                if keep(&self.convert_module(entry)) {
+                    table.entries[count] = table.entries[index];
+                    table.set_foreign(count, foreign);
+                    count += 1;
+                } else if !foreign {
+                    // the copy owns the string now
+                    self.free_string(table.entries[index].string);
+                }
+            }
+            self.replace_module_table(table, count);
+        }
+        Ok(())
+    }
+
+    /// Allocate memory for a module table with `count` entries.
+    ///
+    /// If `foreign_strings` is set, there is a bitmap to mark the strings that
+    /// weren't allocated here, no string is marked yet.
+    /// Returns address 0 for empty tables.
+    unsafe fn allocate_module_table(
+        &mut self,
+        count: usize,
+        foreign_strings: bool,
+    ) -> Result<NewModuleTable<'a>, SetError> {
+        if count == 0 {
+            return Ok(NewModuleTable {
+                addr: 0,
+                entries: &mut [],
+                foreign_strings: &mut [],
+            });
+        }
+        if u32::try_from(count).is_err() {
+            return Err(SetError::TooManyEntries);
+        }
+        let table_size = count
+            .checked_mul(size_of::<MBModule>())
+            .ok_or(SetError::TooManyEntries)?;
+        let bitmap_size = if foreign_strings {
+            OwnedModuleTable::bitmap_size(count)
+        } else {
+            0
+        };
+        let request = AllocationRequest::new(
+            table_size
+                .checked_add(bitmap_size)
+                .ok_or(SetError::TooManyEntries)?,
+            AllocationPurpose::ModuleTable,
+        );
+        let (addr, slice) = self.try_allocate(request)?;
+        let (entries, bitmap) = slice.split_at_mut(table_size);
+        bitmap.fill(0);
+        Ok(NewModuleTable {
+            addr,
+            entries: slice::from_raw_parts_mut(entries.as_mut_ptr().cast::<MBModule>(), count),
+            foreign_strings: &mut *(bitmap as *mut [u8]),
+        })
+    }
+
+    /// Allocate a module table for `capacity` entries and copy the current ones into it.
+    ///
+    /// `place` returns the new index of each entry, or `None` to leave it out.
+    /// If this instance didn't allocate the current table, the strings are copied
+    /// as well, so that editing it frees only what was allocated here. Strings
+    /// that can't be read are kept and marked as foreign, they are never freed.
+    /// The current table is left alone, everything is freed again on failure.
+    unsafe fn try_copy_modules<F>(
+        &mut self,
+        capacity: usize,
+        place: F,
+    ) -> Result<NewModuleTable<'a>, SetError>
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let old = self.module_table().unwrap_or(&[]);
+        let copy_strings = self.owned.module_table.is_none();
+        let foreign_strings =
+            (0..old.len()).any(|index| place(index).is_some() && self.keeps_module_string(index));
+        let mut table = self.allocate_module_table(capacity, foreign_strings)?;
+        for (index, entry) in old.iter().enumerate() {
+            let new = match place(index) {
+                Some(new) => new,
+                None => continue,
+            };
+            let string = entry.string;
+            let bytes = if copy_strings && string != 0 {
+                c_string_bytes(self.memory_management, string.into())
+            } else {
+                None
+            };
+            let string = match bytes {
+                Some(bytes) => self.try_convert_to_c_string(Some(bytes)),
+                None => Ok(string),
+            };
+            match string {
+                Ok(string) => {
+                    table.entries[new] = MBModule { string, ..*entry };
+                    let foreign = self.keeps_module_string(index);
+                    table.set_foreign(new, foreign);
+                }
+                Err(e) => {
+                    for (index, entry) in old[..index].iter().enumerate() {
+                        match place(index) {
+                            Some(new) if table.entries[new].string != entry.string => {
+                                self.free_string(table.entries[new].string)
+                            }
+                            _ => {}
+                        }
+                    }
+                    self.free(table.addr, Some(table.owned().size()));
+                    return Err(e);
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// Check whether a copy of the current module table has to keep the string
+    /// of the module at `index`, although it wasn't allocated here.
+    unsafe fn keeps_module_string(&self, index: usize) -> bool {
+        let string = self.module_table().unwrap_or(&[])[index].string;
+        if string == 0 {
+            false
+        } else if self.owned.module_table.is_some() {
+            !self.module_string_owned(index)
+        } else {
+            c_string_bytes(self.memory_management, string.into()).is_none()
+        }
+    }
+
+    /// Check whether this instance allocated the string of the module at `index`
+    /// in the current table.
+    unsafe fn module_string_owned(&self, index: usize) -> bool {
+        match self.owned.module_table {
+            None => false,
+            Some(OwnedModuleTable {
+                foreign_strings: false,
+                ..
+            }) => true,
+            Some(table) => {
+                let bitmap = (table.capacity * size_of::<MBModule>() + index / 8) as PAddr;
+                self.memory_management
+                    .paddr_to_slice(PAddr::from(self.header.mods_addr) + bitmap, 1)
+                    .is_some_and(|byte| byte[0] & (1 << (index % 8)) == 0)
+            }
+        }
+    }
+
+    /// Free the current module table (but not the strings) and use the given one.
+    ///
+    /// The first `count` entries of the new table are used.
+    unsafe fn replace_module_table(&mut self, table: NewModuleTable, count: usize) {
+        let old = self.owned.module_table.take();
+        self.free(self.header.mods_addr, old.map(|old| old.size()));
+        if count == 0 {
+            self.free(table.addr, Some(table.owned().size()));
+        } else {
+            self.owned.module_table = Some(table.owned());
+        }
+        self.set_has_modules(count > 0);
+        self.header.mods_addr = if count > 0 { table.addr } else { 0 };
+        // allocate_module_table checked that the count fits
+        self.header.mods_count = count as u32;
+    }
+
+    /// Free the string of the module at `index` in the current table
+    /// if this instance allocated it.
+    unsafe fn free_module_string(&mut self, index: usize) {
+        if self.module_string_owned(index) {
+            let string = self.module_table().unwrap_or(&[])[index].string;
+            self.free_string(string);
+        }
+    }
+
+    /// Free a string this instance allocated.
+    unsafe fn free_string(&mut self, string: u32) {
+        if string == 0 {
+            return;
+        }
+        if let Some(range) = self.c_string_range(string.into()) {
+            self.free(string, Some((range.end - range.start) as usize));
+        }
+    }
+
+    /// Publish a copy of a memory map to the kernel.
+    ///
+    /// `map` contains the entries in the format of the Multiboot memory map,
+    /// each one starting with its size. It's copied to newly allocated memory.
+    /// The information is left unchanged on failure.
+    pub fn try_copy_memory_map(&mut self, map: Option<&[u8]>) -> Result<(), SetError> {
+        self.try_copy_memory_map_with(map.map(|map| {
+            let write = move |buffer: &mut [u8]| buffer.copy_from_slice(map);
+            (map.len(), write)
+        }))
+    }
+
+    /// Allocate `length` bytes for the memory map, fill them using `write`
+    /// and publish them.
+    fn try_copy_memory_map_with<F: FnOnce(&mut [u8])>(
+        &mut self,
+        map: Option<(usize, F)>,
+    ) -> Result<(), SetError> {
+        let has_memory_map = map.is_some();
+        if let Some((length, write)) = map {
+            let length32 = length.try_into().map_err(|_| SetError::TooManyEntries)?;
+            let request = AllocationRequest::new(length, AllocationPurpose::MemoryMap);
+            let (addr, buffer) = unsafe { self.try_allocate(request)? };
+            write(&mut buffer[..length]);
+            self.free_memory_map();
+            self.owned.memory_map = Some(length);
+            self.header.mmap_addr = addr;
+            self.header.mmap_length = length32;
+        } else {
+            self.free_memory_map();
+        }
+        self.set_has_memory_map(has_memory_map);
+        Ok(())
+    }
+
+    /// Publish the memory map and the memory bounds derived from it to the kernel.
+    ///
+    /// The entries are copied to newly allocated memory and the bounds
+    /// are computed using [`memory_bounds`], so both are always consistent.
+    ///
+    /// [`memory_bounds`]: fn.memory_bounds.html
+    pub fn set_memory_map_and_bounds(&mut self, regions: Option<&[MemoryEntry]>) {
+        self.try_set_memory_map_and_bounds(regions).unwrap()
+    }
+
+    /// Publish the memory map and the memory bounds derived from it to the kernel.
+    ///
+    /// Like [`set_memory_map_and_bounds`], but the information is left unchanged on failure.
+    ///
+    /// [`set_memory_map_and_bounds`]: #method.set_memory_map_and_bounds
+    pub fn try_set_memory_map_and_bounds(
+        &mut self,
+        regions: Option<&[MemoryEntry]>,
+    ) -> Result<(), SetError> {
+        // the entries are packed, so they already are in the right format
+        self.try_copy_memory_map(regions.map(as_bytes))?;
+        self.set_memory_bounds(regions.map(memory_bounds));
+        Ok(())
+    }
+
+    /// Publish an E820 memory map to the kernel.
+    ///
+    /// The entries are copied to newly allocated memory and keep their size,
+    /// so the extended attributes of 24 byte entries are preserved.
+    ///
+    /// Use [`E820Entry::from`] on the items of [`MemoryMapIter::with_extended_attributes`]
+    /// to get the map back.
+    ///
+    /// [`E820Entry::from`]: ../e820/struct.E820Entry.html
+    /// [`MemoryMapIter::with_extended_attributes`]: struct.MemoryMapIter.html#method.with_extended_attributes
+    pub fn set_e820_memory_map(&mut self, entries: Option<&[E820Entry]>) {
+        self.try_set_e820_memory_map(entries).unwrap()
+    }
+
+    /// Publish an E820 memory map to the kernel.
+    ///
+    /// Like [`set_e820_memory_map`], but the information is left unchanged on failure.
+    ///
+    /// [`set_e820_memory_map`]: #method.set_e820_memory_map
+    pub fn try_set_e820_memory_map(
+        &mut self,
+        entries: Option<&[E820Entry]>,
+    ) -> Result<(), SetError> {
+        self.try_copy_memory_map_with(entries.map(|entries| {
+            let write = move |buffer: &mut [u8]| {
+                e820::write_memory_map(entries.iter().cloned(), buffer).unwrap();
+            };
+            (e820::memory_map_size(entries.iter().cloned()), write)
+        }))
+    }
 }
 
 /// The ‘boot_device’ field.
@@ -678,18 +2197,58 @@ impl Default for BootDevice {
 }
 
 /// Types that define if the memory is usable or not.
-#[derive(Debug, PartialEq, Eq)]
+///
+/// These are the address range types defined by ACPI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MemoryType {
     /// memory, available to OS
-    Available = 1,
+    Available,
     /// reserved, not available (rom, mem map dev)
-    Reserved = 2,
+    Reserved,
     /// ACPI Reclaim Memory
-    ACPI = 3,
+    ACPI,
     /// ACPI NVS Memory
-    NVS = 4,
+    NVS,
     /// defective RAM modules
-    Defect = 5,
+    Defect,
+    /// disabled memory, not available (ACPI 4.0)
+    Disabled,
+    /// persistent memory (ACPI 6.0)
+    Persistent,
+    /// any other type, e.g. a vendor specific one
+    ///
+    /// Values that have their own variant are never parsed into this.
+    Other(u32),
+}
+
+impl From<u32> for MemoryType {
+    fn from(mtype: u32) -> Self {
+        match mtype {
+            1 => MemoryType::Available,
+            2 => MemoryType::Reserved,
+            3 => MemoryType::ACPI,
+            4 => MemoryType::NVS,
+            5 => MemoryType::Defect,
+            6 => MemoryType::Disabled,
+            7 => MemoryType::Persistent,
+            other => MemoryType::Other(other),
+        }
+    }
+}
+
+impl From<MemoryType> for u32 {
+    fn from(ty: MemoryType) -> Self {
+        match ty {
+            MemoryType::Available => 1,
+            MemoryType::Reserved => 2,
+            MemoryType::ACPI => 3,
+            MemoryType::NVS => 4,
+            MemoryType::Defect => 5,
+            MemoryType::Disabled => 6,
+            MemoryType::Persistent => 7,
+            MemoryType::Other(other) => other,
+        }
+    }
 }
 
 /// Multiboot format of the MMAP buffer.
@@ -739,61 +2298,563 @@ impl MemoryEntry {
             size,
             base_addr,
             length,
-            mtype: ty as u32,
+            mtype: ty.into(),
+        }
+    }
+
+    /// Create a new entry that also contains ACPI 3.0 extended attributes.
+    ///
+    /// This creates a struct with a size of 24 bytes.
+    pub fn with_extended_attributes(
+        base_addr: PAddr,
+        length: PAddr,
+        ty: MemoryType,
+        extended_attributes: u32,
+    ) -> ExtendedMemoryEntry {
+        let mut entry = Self::new(base_addr, length, ty);
+        entry.size = (size_of::<ExtendedMemoryEntry>() - size_of::<u32>())
+            .try_into()
+            .unwrap();
+        ExtendedMemoryEntry {
+            entry,
+            extended_attributes,
+        }
+    }
+
+    /// Get base of memory region.
+    pub fn base_address(&self) -> PAddr {
+        self.base_addr as PAddr
+    }
+
+    /// Get size of the memory region.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// Is the region type valid RAM?
+    pub fn memory_type(&self) -> MemoryType {
+        self.mtype.into()
+    }
+
+    /// Get the size of the entry, not counting the size field itself.
+    ///
+    /// This is 20 for plain entries and at least 24 if the entry
+    /// contains extended attributes.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/// Bit 0 of the extended attributes: If it's clear, the entry should be ignored.
+pub const EXTENDED_ATTRIBUTE_ENABLED: u32 = 1 << 0;
+/// Bit 1 of the extended attributes: The memory is non-volatile.
+pub const EXTENDED_ATTRIBUTE_NON_VOLATILE: u32 = 1 << 1;
+
+/// Multiboot format of a MMAP buffer entry with ACPI 3.0 extended attributes.
+///
+/// Create this using [`MemoryEntry::with_extended_attributes`].
+///
+/// [`MemoryEntry::with_extended_attributes`]: struct.MemoryEntry.html#method.with_extended_attributes
+#[derive(Clone, Copy, Debug)]
+#[repr(C, packed)]
+pub struct ExtendedMemoryEntry {
+    entry: MemoryEntry,
+    extended_attributes: u32,
+}
+
+impl ExtendedMemoryEntry {
+    /// Get the plain entry.
+    pub fn entry(&self) -> &MemoryEntry {
+        &self.entry
+    }
+
+    /// Get the extended attributes.
+    pub fn extended_attributes(&self) -> u32 {
+        self.extended_attributes
+    }
+}
+
+/// Used to iterate over all memory regions provided by multiboot.
+pub struct MemoryMapIter<'a, 'b, M: ?Sized + 'b = dyn MemoryManagement + 'b> {
+    mb: &'a Multiboot<'a, 'b, M>,
+    current: u32,
+    end: u32,
+}
+
+impl<'a, 'b, M: ?Sized> Clone for MemoryMapIter<'a, 'b, M> {
+    fn clone(&self) -> Self {
+        Self {
+            mb: self.mb,
+            current: self.current,
+            end: self.end,
+        }
+    }
+}
+
+impl<'a, 'b, M: PhysicalMemoryMapper + ?Sized> Iterator for MemoryMapIter<'a, 'b, M> {
+    type Item = &'a MemoryEntry;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a MemoryEntry> {
+        if self.current < self.end {
+            unsafe {
+                self.mb
+                    .cast(self.current as PAddr)
+                    .inspect(|region: &&'a MemoryEntry| {
+                        self.current += region.size + 4;
+                    })
+            }
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, 'b, M: PhysicalMemoryMapper + ?Sized> MemoryMapIter<'a, 'b, M> {
+    /// Also return the ACPI 3.0 extended attributes of each entry.
+    ///
+    /// The attributes are `None` if an entry is too small to contain them.
+    pub fn with_extended_attributes(self) -> ExtendedMemoryMapIter<'a, 'b, M> {
+        ExtendedMemoryMapIter { inner: self }
+    }
+}
+
+/// Used to iterate over all memory regions and their extended attributes.
+#[derive(Clone)]
+pub struct ExtendedMemoryMapIter<'a, 'b, M: ?Sized + 'b = dyn MemoryManagement + 'b> {
+    inner: MemoryMapIter<'a, 'b, M>,
+}
+
+impl<'a, 'b, M: PhysicalMemoryMapper + ?Sized> Iterator for ExtendedMemoryMapIter<'a, 'b, M> {
+    type Item = (&'a MemoryEntry, Option<u32>);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a MemoryEntry, Option<u32>)> {
+        let addr = PAddr::from(self.inner.current);
+        let entry = self.inner.next()?;
+        let attributes = if entry.size >= 24 {
+            // the attributes might not be aligned
+            unsafe {
+                self.inner
+                    .mb
+                    .memory_management
+                    .paddr_to_slice(addr + size_of::<MemoryEntry>() as PAddr, 4)
+            }
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        } else {
+            None
+        };
+        Some((entry, attributes))
+    }
+}
+
+/// Compute `mem_lower` and `mem_upper` (in KiB) from a memory map.
+///
+/// Lower memory is the available memory starting at address 0, up to 640 KiB.
+/// Upper memory is the contiguous available memory starting at 1 MiB,
+/// up to the first hole. Entries may be unsorted; available entries that touch
+/// are treated as one region and other entries punch holes into them.
+pub fn memory_bounds<'e, I>(regions: I) -> (u32, u32)
+where
+    I: IntoIterator<Item = &'e MemoryEntry>,
+    I::IntoIter: Clone,
+{
+    const KIB: PAddr = 1024;
+    let regions = regions.into_iter();
+    let lower = contiguous_end(regions.clone(), 0).min(640 * KIB) / KIB;
+    let upper = (contiguous_end(regions, 1024 * KIB).saturating_sub(1024 * KIB)) / KIB;
+    (lower as u32, upper.min(u32::MAX.into()) as u32)
+}
+
+/// Get the end of the contiguous available memory starting at `start`.
+///
+/// Returns `start` if it isn't available at all.
+fn contiguous_end<'e, I>(regions: I, start: PAddr) -> PAddr
+where
+    I: Iterator<Item = &'e MemoryEntry> + Clone,
+{
+    let range = |r: &MemoryEntry| r.base_address()..r.base_address().saturating_add(r.length());
+    let mut end = start;
+    while let Some(next) = regions
+        .clone()
+        .filter(|r| r.memory_type() == MemoryType::Available)
+        .map(range)
+        .filter(|r| r.start <= end && r.end > end)
+        .map(|r| r.end)
+        .max()
+    {
+        end = next;
+    }
+    regions
+        .filter(|r| r.memory_type() != MemoryType::Available)
+        .map(range)
+        .filter(|r| r.start < end && r.end > start)
+        .map(|r| r.start.max(start))
+        .fold(end, PAddr::min)
+}
+
+/// The memory bounds claim more memory than the memory map contains.
+///
+/// This is returned by [`Multiboot::check_memory_bounds`].
+///
+/// [`Multiboot::check_memory_bounds`]: struct.Multiboot.html#method.check_memory_bounds
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryBoundsMismatch {
+    /// `mem_lower` and `mem_upper` as found in the information.
+    pub bounds: (u32, u32),
+    /// `mem_lower` and `mem_upper` as computed from the memory map.
+    pub memory_map: (u32, u32),
+}
+
+/// The longest string [`Multiboot::validate`] accepts, without the terminating zero.
+///
+/// [`Multiboot::validate`]: struct.Multiboot.html#method.validate
+pub const MAX_STRING_LENGTH: usize = 4096;
+
+/// The number of issues a [`ValidationReport`] stores.
+///
+/// [`ValidationReport`]: struct.ValidationReport.html
+pub const MAX_VALIDATION_ISSUES: usize = 16;
+
+/// A problem found by [`Multiboot::validate`].
+///
+/// [`Multiboot::validate`]: struct.Multiboot.html#method.validate
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// The data can't be translated by the memory management.
+    Unmapped(OccupiedRegionKind),
+    /// The string has no terminating zero within [`MAX_STRING_LENGTH`] bytes.
+    ///
+    /// [`MAX_STRING_LENGTH`]: constant.MAX_STRING_LENGTH.html
+    UnterminatedString(OccupiedRegionKind),
+    /// The string isn't valid UTF-8.
+    InvalidUtf8(OccupiedRegionKind),
+    /// `mmap_length` isn't a multiple of the entry stride.
+    MemoryMapLength,
+    /// The size of the memory map entry with the given index is smaller than 20.
+    MemoryEntryTooSmall(usize),
+    /// The module with the given index ends before it starts.
+    InvalidModuleRange(usize),
+    /// The module with the given index overlaps the [`MultibootInfo`] struct.
+    ///
+    /// [`MultibootInfo`]: struct.MultibootInfo.html
+    ModuleOverlapsInfo(usize),
+    /// The framebuffer pitch is smaller than width × bpp / 8.
+    FramebufferPitchTooSmall,
+    /// `mem_lower` is larger than 640 KiB.
+    LowerMemoryTooLarge(u32),
+    /// Both a.out and ELF symbols are present.
+    ConflictingSymbols,
+}
+
+/// The result of [`Multiboot::validate`].
+///
+/// The first [`MAX_VALIDATION_ISSUES`] issues are stored, the rest is only counted.
+///
+/// Whether modules overlap the information struct is only checked if the
+/// information was created with [`Multiboot::from_ptr`], [`Multiboot::from_ref`]
+/// doesn't know its address.
+///
+/// [`Multiboot::validate`]: struct.Multiboot.html#method.validate
+/// [`Multiboot::from_ptr`]: struct.Multiboot.html#method.from_ptr
+/// [`Multiboot::from_ref`]: struct.Multiboot.html#method.from_ref
+/// [`MAX_VALIDATION_ISSUES`]: constant.MAX_VALIDATION_ISSUES.html
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    issues: [Option<ValidationIssue>; MAX_VALIDATION_ISSUES],
+    count: usize,
+}
+
+impl ValidationReport {
+    fn push(&mut self, issue: ValidationIssue) {
+        if let Some(slot) = self.issues.get_mut(self.count) {
+            *slot = Some(issue);
+        }
+        self.count += 1;
+    }
+
+    /// Were no issues found?
+    pub fn is_valid(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Get the stored issues in the order they were found.
+    pub fn issues(&self) -> impl Iterator<Item = ValidationIssue> + '_ {
+        self.issues.iter().flatten().cloned()
+    }
+
+    /// Get the number of issues that were found but not stored.
+    pub fn missed(&self) -> usize {
+        self.count.saturating_sub(MAX_VALIDATION_ISSUES)
+    }
+}
+
+/// Changing the information failed.
+///
+/// This is returned by the `try_set_*` methods of [`Multiboot`],
+/// which leave the information unchanged in this case.
+///
+/// [`Multiboot`]: struct.Multiboot.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SetError {
+    /// The memory management couldn't allocate memory.
+    AllocationFailed,
+    /// An address doesn't fit into 32 bits.
+    AddressTooLarge,
+    /// There are too many entries for the length to fit into 32 bits.
+    TooManyEntries,
+    /// A string contains a zero byte, so it can't be terminated by one.
+    InteriorNul,
+}
+
+/// Get the range of `length` bytes starting at the 32 bit address `addr`.
+fn phys_range(addr: u32, length: PAddr) -> Range<PAddr> {
+    PAddr::from(addr)..PAddr::from(addr) + length
+}
+
+/// The number of ranges a [`FreeMemoryIter`] collects when it is created.
+///
+/// If there are more, they are searched again for every free range.
+///
+/// [`FreeMemoryIter`]: struct.FreeMemoryIter.html
+const MAX_USED_RANGES: usize = 64;
+
+/// The ranges that may not be used, sorted by their start.
+struct UsedRanges {
+    ranges: [(PAddr, PAddr); MAX_USED_RANGES],
+    count: usize,
+    /// Whether all ranges fit.
+    complete: bool,
+}
+
+impl Default for UsedRanges {
+    fn default() -> Self {
+        Self {
+            ranges: [(0, 0); MAX_USED_RANGES],
+            count: 0,
+            complete: true,
         }
     }
+}
 
-    /// Get base of memory region.
-    pub fn base_address(&self) -> PAddr {
-        self.base_addr as PAddr
+/// Used to iterate over all memory that is available and not used by boot data.
+pub struct FreeMemoryIter<'a, 'b, 'r, M: ?Sized + 'b = dyn MemoryManagement + 'b> {
+    mb: &'a Multiboot<'a, 'b, M>,
+    regions: MemoryMapIter<'a, 'b, M>,
+    reserved: &'r [Range<PAddr>],
+    used: UsedRanges,
+    /// The part of the current available region that hasn't been handled yet.
+    current: Range<PAddr>,
+}
+
+impl<'a, 'b, 'r, M: PhysicalMemoryMapper + ?Sized> FreeMemoryIter<'a, 'b, 'r, M> {
+    /// Call `f` for every range that may not be used.
+    fn for_each_used<F: FnMut(Range<PAddr>)>(&self, f: F) {
+        let unavailable = self
+            .mb
+            .memory_regions()
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.memory_type() != MemoryType::Available)
+            .map(|entry| entry.base_address()..entry.base_address().saturating_add(entry.length()));
+        self.mb
+            .occupied_regions()
+            .map(|region| region.range)
+            .chain(self.reserved.iter().cloned())
+            .chain(unavailable)
+            .filter(|used| used.start < used.end)
+            .for_each(f)
     }
 
-    /// Get size of the memory region.
-    pub fn length(&self) -> u64 {
-        self.length
+    /// Collect the ranges that may not be used, so they don't have to be searched again.
+    fn collect_used(&mut self) {
+        let mut used = UsedRanges::default();
+        self.for_each_used(|range| {
+            if used.count == MAX_USED_RANGES {
+                used.complete = false;
+                return;
+            }
+            // insertion sort, there usually are only a few ranges
+            let index =
+                used.ranges[..used.count].partition_point(|&(start, _)| start <= range.start);
+            used.ranges.copy_within(index..used.count, index + 1);
+            used.ranges[index] = (range.start, range.end);
+            used.count += 1;
+        });
+        self.used = used;
     }
 
-    /// Is the region type valid RAM?
-    pub fn memory_type(&self) -> MemoryType {
-        match self.mtype {
-            1 => MemoryType::Available,
-            3 => MemoryType::ACPI,
-            4 => MemoryType::NVS,
-            5 => MemoryType::Defect,
-            _ => MemoryType::Reserved,
+    /// Find the lowest range that may not be used and overlaps with `range`.
+    fn first_used(&self, range: &Range<PAddr>) -> Option<Range<PAddr>> {
+        let overlaps = |used: &Range<PAddr>| used.start < range.end && used.end > range.start;
+        if self.used.complete {
+            return self.used.ranges[..self.used.count]
+                .iter()
+                .map(|&(start, end)| start..end)
+                .find(overlaps);
         }
+        let mut first: Option<Range<PAddr>> = None;
+        self.for_each_used(|used| {
+            if overlaps(&used) {
+                match first {
+                    Some(ref first) if first.start <= used.start => {}
+                    _ => first = Some(used),
+                }
+            }
+        });
+        first
     }
 }
 
-/// Used to iterate over all memory regions provided by multiboot.
-pub struct MemoryMapIter<'a, 'b> {
-    mb: &'a Multiboot<'a, 'b>,
-    current: u32,
-    end: u32,
+impl<'a, 'b, 'r, M: PhysicalMemoryMapper + ?Sized> Iterator for FreeMemoryIter<'a, 'b, 'r, M> {
+    type Item = Range<PAddr>;
+
+    fn next(&mut self) -> Option<Range<PAddr>> {
+        loop {
+            if self.current.start >= self.current.end {
+                let region = self
+                    .regions
+                    .find(|entry| entry.memory_type() == MemoryType::Available)?;
+                let start = region.base_address();
+                self.current = start..start.saturating_add(region.length());
+                continue;
+            }
+            let free = match self.first_used(&self.current) {
+                None => {
+                    let free = self.current.clone();
+                    self.current.start = self.current.end;
+                    free
+                }
+                Some(used) if used.start <= self.current.start => {
+                    self.current.start = used.end;
+                    continue;
+                }
+                Some(used) => {
+                    let free = self.current.start..used.start;
+                    self.current.start = used.start;
+                    free
+                }
+            };
+            let start = round_up!(free.start, PAGE_SIZE);
+            let end = round_down!(free.end, PAGE_SIZE);
+            if start < end {
+                return Some(start..end);
+            }
+        }
+    }
 }
 
-impl<'a, 'b> Iterator for MemoryMapIter<'a, 'b> {
-    type Item = &'a MemoryEntry;
+/// Kinds of data the Multiboot information refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OccupiedRegionKind {
+    /// The [`MultibootInfo`] struct itself.
+    ///
+    /// [`MultibootInfo`]: struct.MultibootInfo.html
+    Info,
+    /// The command line string.
+    CommandLine,
+    /// The boot loader name string.
+    BootLoaderName,
+    /// The table describing all modules.
+    ModuleTable,
+    /// The contents of the module with the given index.
+    Module(usize),
+    /// The string of the module with the given index.
+    ModuleString(usize),
+    /// The memory map buffer.
+    MemoryMap,
+    /// The drives table.
+    Drives,
+    /// The ELF section header table.
+    ElfSectionTable,
+    /// The contents of the ELF section with the given index,
+    /// if the bootloader copied it into memory.
+    ElfSection(u32),
+    /// The AOut symbol and string tables.
+    AOutSymbols,
+    /// The APM table.
+    ApmTable,
+    /// The VBE control information.
+    VbeControlInfo,
+    /// The VBE mode information.
+    VbeModeInfo,
+    /// The palette of an indexed color framebuffer.
+    Palette,
+}
 
-    #[inline]
-    fn next(&mut self) -> Option<&'a MemoryEntry> {
-        if self.current < self.end {
-            unsafe {
-                self.mb
-                    .cast(self.current as PAddr)
-                    .map(|region: &'a MemoryEntry| {
-                        self.current += region.size + 4;
-                        region
-                    })
+/// A range of physical memory that is occupied by Multiboot information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OccupiedRegion {
+    /// What is stored in this range.
+    pub kind: OccupiedRegionKind,
+    /// The physical addresses.
+    pub range: Range<PAddr>,
+}
+
+/// Used to iterate over all memory occupied by multiboot.
+pub struct OccupiedRegionIter<'a, 'b, M: ?Sized + 'b = dyn MemoryManagement + 'b> {
+    mb: &'a Multiboot<'a, 'b, M>,
+    /// Regions that exist at most once.
+    fixed: [Option<OccupiedRegion>; 11],
+    fixed_index: usize,
+    /// Modules that haven't been handled yet.
+    mods: &'a [MBModule],
+    module_index: usize,
+    /// The string of the module that has been returned last.
+    module_string: Option<OccupiedRegion>,
+    section_index: u32,
+}
+
+impl<'a, 'b, M: PhysicalMemoryMapper + ?Sized> Iterator for OccupiedRegionIter<'a, 'b, M> {
+    type Item = OccupiedRegion;
+
+    fn next(&mut self) -> Option<OccupiedRegion> {
+        while let Some(region) = self.fixed.get_mut(self.fixed_index) {
+            self.fixed_index += 1;
+            if let Some(region) = region.take() {
+                return Some(region);
+            }
+        }
+        if let Some(region) = self.module_string.take() {
+            return Some(region);
+        }
+        if let Some((first, rest)) = self.mods.split_first() {
+            let index = self.module_index;
+            self.mods = rest;
+            self.module_index += 1;
+            self.module_string =
+                unsafe { self.mb.c_string_range(first.string.into()) }.map(|range| {
+                    OccupiedRegion {
+                        kind: OccupiedRegionKind::ModuleString(index),
+                        range,
+                    }
+                });
+            return Some(OccupiedRegion {
+                kind: OccupiedRegionKind::Module(index),
+                range: first.start.into()..first.end.into(),
+            });
+        }
+        if let Some(SymbolType::Elf(symbols)) = self.mb.symbols() {
+            while self.section_index < symbols.num {
+                let index = self.section_index;
+                self.section_index += 1;
+                if let Some(range) = self.mb.elf_section_range(&symbols, index) {
+                    return Some(OccupiedRegion {
+                        kind: OccupiedRegionKind::ElfSection(index),
+                        range,
+                    });
+                }
             }
-        } else {
-            None
         }
+        None
     }
 }
 
 /// Multiboot format to information about module
-#[repr(C)]
+///
+/// Nothing guarantees that the table is aligned, so this is packed.
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
 struct MBModule {
     /// Start address of module in memory.
     start: u32,
@@ -818,55 +2879,173 @@ struct MBModule {
 
 impl Debug for MBModule {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (start, end, string, reserved) = (self.start, self.end, self.string, self.reserved);
         write!(
             f,
             "MBModule {{ start: {}, end: {}, string: {}, reserved: {} }}",
-            self.start, self.end, self.string, self.reserved
+            start, end, string, reserved
         )
     }
 }
 
 /// Information about a module in multiboot.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Module<'a> {
     /// Start address of module in physical memory.
     pub start: PAddr,
     /// End address of module in physic memory.
     pub end: PAddr,
     /// Name of the module.
+    ///
+    /// This is `None` if the string isn't valid UTF-8,
+    /// see [`string_bytes`](#method.string_bytes).
     pub string: Option<&'a str>,
+    /// The string of the module if it isn't valid UTF-8.
+    raw_string: Option<&'a [u8]>,
 }
 
 impl<'a> Module<'a> {
-    pub fn new(start: PAddr, end: PAddr, name: Option<&'a str>) -> Module {
+    pub fn new(start: PAddr, end: PAddr, name: Option<&'a str>) -> Module<'a> {
         Module {
             start,
             end,
             string: name,
+            raw_string: None,
         }
     }
+
+    /// Create a module whose string may not be UTF-8.
+    ///
+    /// The string must not contain a zero byte.
+    pub fn from_bytes(start: PAddr, end: PAddr, string: Option<&'a [u8]>) -> Module<'a> {
+        match string.map(str::from_utf8) {
+            Some(Err(_)) => Module {
+                start,
+                end,
+                string: None,
+                raw_string: string,
+            },
+            string => Module::new(start, end, string.and_then(Result::ok)),
+        }
+    }
+
+    /// Get the string of the module, without the terminating zero.
+    ///
+    /// Unlike [`string`](#structfield.string), this works for strings that aren't UTF-8.
+    pub fn string_bytes(&self) -> Option<&'a [u8]> {
+        self.string.map(str::as_bytes).or(self.raw_string)
+    }
+
+    /// Parse the string of the module.
+    ///
+    /// Like the kernel command line, this usually starts with the path of the module.
+    pub fn command_line(&self) -> Option<CommandLine<'a>> {
+        self.string.map(CommandLine::new)
+    }
+
+    /// Get the path of the module, the first word of its string.
+    pub fn path(&self) -> Option<Word<'a>> {
+        self.command_line()?.path()
+    }
+
+    /// Get the arguments of the module, everything after the path.
+    pub fn arguments(&self) -> Option<&'a str> {
+        self.command_line().map(|cmdline| cmdline.arguments())
+    }
+
+    /// Check whether the module is called `name`.
+    ///
+    /// This is the case if `name` is the whole string, the path
+    /// or the last component of the path, e.g. `initrd` for
+    /// `/boot/initrd console=ttyS0`.
+    pub fn has_name(&self, name: &str) -> bool {
+        if self.string == Some(name) {
+            return true;
+        }
+        let path = match self.path() {
+            Some(path) => path,
+            None => return false,
+        };
+        if path == name {
+            return true;
+        }
+        // compare the characters after the last slash
+        let mut chars = path.chars();
+        let mut file_name = chars.clone();
+        while let Some(c) = chars.next() {
+            if c == '/' {
+                file_name = chars.clone();
+            }
+        }
+        !name.is_empty() && file_name.eq(name.chars())
+    }
+
+    /// Get the contents of the module.
+    ///
+    /// Returns `None` if the module ends before it starts or
+    /// `memory_management` can't translate it.
+    ///
+    /// # Safety
+    /// The module has to describe memory that actually contains the module
+    /// and stays intact, e.g. an entry of the module table of a valid
+    /// information. Prefer [`Multiboot::module_data`], which checks this.
+    ///
+    /// [`Multiboot::module_data`]: struct.Multiboot.html#method.module_data
+    pub unsafe fn data<M: PhysicalMemoryMapper + ?Sized>(
+        &self,
+        memory_management: &M,
+    ) -> Option<&'static [u8]> {
+        let length = self.end.checked_sub(self.start)?.try_into().ok()?;
+        memory_management.paddr_to_slice(self.start, length)
+    }
+
+    /// Find out what the module contains by looking at its first bytes.
+    ///
+    /// Only the first [`DETECT_LENGTH`] bytes are mapped.
+    /// Returns [`ModuleKind::Unknown`] if the contents can't be accessed.
+    ///
+    /// [`DETECT_LENGTH`]: ../module_kind/constant.DETECT_LENGTH.html
+    /// [`ModuleKind::Unknown`]: ../module_kind/enum.ModuleKind.html#variant.Unknown
+    ///
+    /// # Safety
+    /// Same as [`data`](#method.data).
+    pub unsafe fn detect_kind<M: PhysicalMemoryMapper + ?Sized>(
+        &self,
+        memory_management: &M,
+    ) -> ModuleKind {
+        let length = match self.end.checked_sub(self.start) {
+            Some(length) => length.min(DETECT_LENGTH as PAddr) as usize,
+            None => return ModuleKind::Unknown,
+        };
+        memory_management
+            .paddr_to_slice(self.start, length)
+            .map_or(ModuleKind::Unknown, ModuleKind::detect)
+    }
 }
 
 /// Used to iterate over all modules in multiboot.
-pub struct ModuleIter<'a, 'b> {
-    mb: &'a Multiboot<'a, 'b>,
+pub struct ModuleIter<'a, 'b, M: ?Sized + 'b = dyn MemoryManagement + 'b> {
+    mb: &'a Multiboot<'a, 'b, M>,
     mods: &'a [MBModule],
 }
 
-impl<'a, 'b> Iterator for ModuleIter<'a, 'b> {
+impl<'a, 'b, M: ?Sized> Clone for ModuleIter<'a, 'b, M> {
+    fn clone(&self) -> Self {
+        Self {
+            mb: self.mb,
+            mods: self.mods,
+        }
+    }
+}
+
+impl<'a, 'b, M: PhysicalMemoryMapper + ?Sized> Iterator for ModuleIter<'a, 'b, M> {
     type Item = Module<'a>;
 
     #[inline]
     fn next(&mut self) -> Option<Module<'a>> {
         self.mods.split_first().map(|(first, rest)| {
             self.mods = rest;
-            unsafe {
-                Module::new(
-                    first.start as PAddr,
-                    first.end as PAddr,
-                    self.mb.convert_c_string(first.string as PAddr),
-                )
-            }
+            unsafe { self.mb.convert_module(first) }
         })
     }
 }
@@ -937,25 +3116,53 @@ impl Debug for ElfSymbols {
     }
 }
 
+impl AOutSymbols {
+    /// The size of both tables and their sizes, if it fits into `usize`.
+    fn tables_size(&self) -> Option<usize> {
+        u64::from(self.tabsize)
+            .checked_add(self.strsize.into())?
+            .checked_add(2 * size_of::<u32>() as u64)?
+            .try_into()
+            .ok()
+    }
+}
+
 impl ElfSymbols {
+    /// The size of the section headers, if it fits into `usize`.
+    fn table_size(&self) -> Option<usize> {
+        u64::from(self.num)
+            .checked_mul(self.size.into())?
+            .try_into()
+            .ok()
+    }
+
     /// Uses a passed address for the symbols.
     ///
     /// Note that the underlying memory has to stay intact.
     ///
     /// Also, this doesn't check whether the supplied parameters are correct.
+    /// Panics if the address doesn't fit into 32 bits,
+    /// see [`try_from_addr`](#method.try_from_addr).
     pub fn from_addr(num: u32, size: u32, addr: PAddr, shndx: u32) -> Self {
-        Self {
+        Self::try_from_addr(num, size, addr, shndx).unwrap()
+    }
+
+    /// Uses a passed address for the symbols, if it fits into 32 bits.
+    ///
+    /// Note that the underlying memory has to stay intact.
+    pub fn try_from_addr(num: u32, size: u32, addr: PAddr, shndx: u32) -> Result<Self, SetError> {
+        Ok(Self {
             num,
             size,
             shndx,
-            addr: addr.try_into().unwrap(),
-        }
+            addr: addr.try_into().map_err(|_| SetError::AddressTooLarge)?,
+        })
     }
 }
 
 /// Contains the information about the framebuffer
 #[repr(C)]
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct FramebufferTable {
     pub addr: u64,
     pub pitch: u32,
@@ -1053,6 +3260,7 @@ pub enum ColorInfoType {
 ///</rawtext>
 /// (If type == 2, it's just text.)
 #[repr(C)]
+#[derive(Copy, Clone)]
 union ColorInfo {
     palette: ColorInfoPalette,
     rgb: ColorInfoRgb,
@@ -1090,3 +3298,500 @@ pub struct ColorInfoRgb {
     pub blue_field_position: u8,
     pub blue_mask_size: u8,
 }
+
+/// A color of an indexed color framebuffer.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ColorDescriptor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+/// Alignment of everything placed by [`MultibootInfoBuilder`].
+///
+/// [`MultibootInfoBuilder`]: struct.MultibootInfoBuilder.html
+const BUILDER_ALIGNMENT: usize = 8;
+
+/// Builds a Multiboot information that is contiguous in memory.
+///
+/// The [`MultibootInfo`] struct is placed at the start of a buffer,
+/// followed by everything it refers to. Nothing is allocated:
+/// [`size`] tells how large the buffer has to be and [`build`] fills it.
+///
+/// ```rust
+/// use multiboot::information::{memory_bounds, MemoryEntry, MemoryType, MultibootInfoBuilder};
+///
+/// let memory_map = [
+///     MemoryEntry::new(0, 0x9fc00, MemoryType::Available),
+///     MemoryEntry::new(0x100000, 0x1f00000, MemoryType::Available),
+/// ];
+/// let builder = MultibootInfoBuilder::new()
+///     .command_line("/boot/kernel quiet")
+///     .memory_bounds(memory_bounds(&memory_map))
+///     .memory_map(&memory_map);
+/// let mut buffer = [0; 512];
+/// assert_eq!(builder.build(&mut buffer, 0x9000), Some(builder.size()));
+/// ```
+///
+/// [`MultibootInfo`]: struct.MultibootInfo.html
+/// [`size`]: #method.size
+/// [`build`]: #method.build
+#[derive(Debug, Default, Clone)]
+pub struct MultibootInfoBuilder<'d> {
+    memory_bounds: Option<(u32, u32)>,
+    boot_device: Option<BootDevice>,
+    // strings are stored without the terminating zero
+    command_line: Option<&'d [u8]>,
+    boot_loader_name: Option<&'d [u8]>,
+    modules: Option<BuilderModules<'d>>,
+    symbols: Option<BuilderSymbols<'d>>,
+    memory_map: Option<&'d [u8]>,
+    drives: Option<&'d [u8]>,
+    config_table: Option<u32>,
+    apm_table: Option<&'d [u8]>,
+    vbe: Option<BuilderVbe<'d>>,
+    framebuffer_table: Option<FramebufferTable>,
+    /// Three bytes per color.
+    palette: Option<&'d [u8]>,
+}
+
+/// Where a [`MultibootInfoBuilder`] gets the modules from.
+///
+/// [`MultibootInfoBuilder`]: struct.MultibootInfoBuilder.html
+#[derive(Copy, Clone)]
+enum BuilderModules<'d> {
+    Slice(&'d [Module<'d>]),
+    /// The module table of an existing information.
+    Table(&'d [MBModule], &'d dyn PhysicalMemoryMapper),
+}
+
+impl<'d> BuilderModules<'d> {
+    fn len(&self) -> usize {
+        match self {
+            BuilderModules::Slice(modules) => modules.len(),
+            BuilderModules::Table(table, _) => table.len(),
+        }
+    }
+
+    /// Get the start, end and string of a module.
+    fn get(&self, index: usize) -> (PAddr, PAddr, Option<&'d [u8]>) {
+        match self {
+            BuilderModules::Slice(modules) => {
+                let module = &modules[index];
+                (module.start, module.end, module.string_bytes())
+            }
+            BuilderModules::Table(table, memory_management) => {
+                let module = &table[index];
+                let string = unsafe { c_string_bytes(*memory_management, module.string.into()) };
+                (module.start.into(), module.end.into(), string)
+            }
+        }
+    }
+}
+
+impl<'d> Debug for BuilderModules<'d> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.len()).map(|index| self.get(index)))
+            .finish()
+    }
+}
+
+/// Symbols and the tables a [`MultibootInfoBuilder`] copies for them.
+///
+/// [`MultibootInfoBuilder`]: struct.MultibootInfoBuilder.html
+#[derive(Debug, Copy, Clone)]
+enum BuilderSymbols<'d> {
+    /// Symbols whose tables aren't copied.
+    Fixed(SymbolType),
+    Elf {
+        headers: &'d [u8],
+        num: u32,
+        size: u32,
+        shndx: u32,
+    },
+    AOut {
+        tables: &'d [u8],
+        tabsize: u32,
+        strsize: u32,
+    },
+}
+
+/// The VBE tables and fields of a [`MultibootInfoBuilder`].
+///
+/// [`MultibootInfoBuilder`]: struct.MultibootInfoBuilder.html
+#[derive(Debug, Copy, Clone)]
+struct BuilderVbe<'d> {
+    control_info: &'d [u8],
+    mode_info: &'d [u8],
+    mode: u16,
+    interface: (u16, u16, u16),
+}
+
+/// View a slice of plain structs as bytes.
+fn as_bytes<T: Copy>(values: &[T]) -> &[u8] {
+    unsafe { slice::from_raw_parts(values.as_ptr().cast::<u8>(), size_of_val(values)) }
+}
+
+impl<'d> MultibootInfoBuilder<'d> {
+    /// Create a builder for an empty information.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the memory bounds (lower, upper).
+    ///
+    /// See [`memory_bounds`](fn.memory_bounds.html) to derive them from a memory map.
+    pub fn memory_bounds(mut self, bounds: (u32, u32)) -> Self {
+        self.memory_bounds = Some(bounds);
+        self
+    }
+
+    /// Set the device the kernel was loaded from.
+    pub fn boot_device(mut self, device: BootDevice) -> Self {
+        self.boot_device = Some(device);
+        self
+    }
+
+    /// Set the command line of the kernel.
+    pub fn command_line(mut self, command_line: &'d str) -> Self {
+        self.command_line = Some(command_line.as_bytes());
+        self
+    }
+
+    /// Set the name of the bootloader.
+    pub fn boot_loader_name(mut self, name: &'d str) -> Self {
+        self.boot_loader_name = Some(name.as_bytes());
+        self
+    }
+
+    /// Set the modules.
+    ///
+    /// The module table and the strings are copied, the modules themselves are not.
+    pub fn modules(mut self, modules: &'d [Module<'d>]) -> Self {
+        self.modules = Some(BuilderModules::Slice(modules));
+        self
+    }
+
+    /// Set the symbols, the memory they refer to isn't copied.
+    ///
+    /// Use [`elf_sections`] or [`aout_symbols`] to copy the tables.
+    ///
+    /// [`elf_sections`]: #method.elf_sections
+    /// [`aout_symbols`]: #method.aout_symbols
+    pub fn symbols(mut self, symbols: SymbolType) -> Self {
+        self.symbols = Some(BuilderSymbols::Fixed(symbols));
+        self
+    }
+
+    /// Copy the ELF section header table, which consists of headers of `entry_size` bytes.
+    ///
+    /// `shndx` is the index of the section containing the section names.
+    /// The sections themselves aren't copied.
+    pub fn elf_sections(mut self, headers: &'d [u8], entry_size: u32, shndx: u32) -> Self {
+        self.symbols = Some(BuilderSymbols::Elf {
+            headers,
+            num: (headers.len() / entry_size.max(1) as usize) as u32,
+            size: entry_size,
+            shndx,
+        });
+        self
+    }
+
+    /// Copy the a.out symbol and string tables.
+    ///
+    /// `tables` starts with the size of the symbol table, followed by the symbols,
+    /// the size of the string table and the strings.
+    pub fn aout_symbols(mut self, tables: &'d [u8], tabsize: u32, strsize: u32) -> Self {
+        self.symbols = Some(BuilderSymbols::AOut {
+            tables,
+            tabsize,
+            strsize,
+        });
+        self
+    }
+
+    /// Set the memory map.
+    pub fn memory_map(mut self, memory_map: &'d [MemoryEntry]) -> Self {
+        // the entries are packed, so there is no padding
+        self.memory_map = Some(as_bytes(memory_map));
+        self
+    }
+
+    /// Set the drives, which are the raw drive structures described in the specification.
+    pub fn drives(mut self, drives: &'d [u8]) -> Self {
+        self.drives = Some(drives);
+        self
+    }
+
+    /// Set the address of the ROM configuration table, which isn't copied.
+    pub fn config_table(mut self, addr: u32) -> Self {
+        self.config_table = Some(addr);
+        self
+    }
+
+    /// Copy the APM table.
+    pub fn apm_table(mut self, table: &'d [u8]) -> Self {
+        self.apm_table = Some(table);
+        self
+    }
+
+    /// Copy the VBE control and mode information and set the VBE mode.
+    ///
+    /// `interface` contains the segment, offset and length of the protected mode interface.
+    pub fn vbe(
+        mut self,
+        control_info: &'d [u8],
+        mode_info: &'d [u8],
+        mode: u16,
+        interface: (u16, u16, u16),
+    ) -> Self {
+        self.vbe = Some(BuilderVbe {
+            control_info,
+            mode_info,
+            mode,
+            interface,
+        });
+        self
+    }
+
+    /// Set the framebuffer table.
+    pub fn framebuffer_table(mut self, table: FramebufferTable) -> Self {
+        self.framebuffer_table = Some(table);
+        self
+    }
+
+    /// Set the palette of an indexed color framebuffer.
+    ///
+    /// This is only used if there is a framebuffer table,
+    /// whose color info is then replaced.
+    pub fn palette(mut self, palette: &'d [ColorDescriptor]) -> Self {
+        self.palette = Some(as_bytes(palette));
+        self
+    }
+
+    fn palette_to_copy(&self) -> Option<&'d [u8]> {
+        self.framebuffer_table.as_ref().and(self.palette)
+    }
+
+    /// Get the number of bytes [`build`](#method.build) writes.
+    pub fn size(&self) -> usize {
+        let piece =
+            |bytes: Option<&[u8]>| bytes.map_or(0, |b| round_up!(b.len(), BUILDER_ALIGNMENT));
+        let string =
+            |bytes: Option<&[u8]>| bytes.map_or(0, |b| round_up!(b.len() + 1, BUILDER_ALIGNMENT));
+        let modules = self.modules.map_or(0, |modules| {
+            round_up!(modules.len() * size_of::<MBModule>(), BUILDER_ALIGNMENT)
+                + (0..modules.len())
+                    .map(|index| string(modules.get(index).2))
+                    .sum::<usize>()
+        });
+        let symbols = match self.symbols {
+            Some(BuilderSymbols::Elf { headers, .. }) => piece(Some(headers)),
+            Some(BuilderSymbols::AOut { tables, .. }) => piece(Some(tables)),
+            _ => 0,
+        };
+        let vbe = self.vbe.map_or(0, |vbe| {
+            piece(Some(vbe.control_info)) + piece(Some(vbe.mode_info))
+        });
+        round_up!(size_of::<MultibootInfo>(), BUILDER_ALIGNMENT)
+            + string(self.command_line)
+            + string(self.boot_loader_name)
+            + modules
+            + symbols
+            + piece(self.memory_map)
+            + piece(self.drives)
+            + piece(self.apm_table)
+            + vbe
+            + piece(self.palette_to_copy())
+    }
+
+    /// Write the information to `buffer`, which is located at `base`.
+    ///
+    /// All pointers are relative to `base`, which is usually a physical address.
+    /// The [`MultibootInfo`] struct ends up at `base`.
+    /// Returns the number of bytes written, which is the same as [`size`],
+    /// or `None` if the buffer is too small or doesn't fit below 4 GiB.
+    ///
+    /// [`MultibootInfo`]: struct.MultibootInfo.html
+    /// [`size`]: #method.size
+    pub fn build(&self, buffer: &mut [u8], base: PAddr) -> Option<usize> {
+        let size = self.size();
+        if buffer.len() < size || base.checked_add(size as PAddr)? > PAddr::from(u32::MAX) + 1 {
+            return None;
+        }
+        let palette = match self.palette_to_copy() {
+            Some(palette) => Some((palette, (palette.len() / 3).try_into().ok()?)),
+            None => None,
+        };
+        let buffer = &mut buffer[..size];
+        buffer.iter_mut().for_each(|b| *b = 0);
+        let mut cursor = BuilderCursor {
+            buffer,
+            base,
+            offset: 0,
+        };
+        let info_offset = cursor.reserve(size_of::<MultibootInfo>()).1;
+
+        let mut info = MultibootInfo::default();
+        let mut no_memory = NoMemory;
+        let mut multiboot = Multiboot::from_ref(&mut info, &mut no_memory);
+        multiboot.set_memory_bounds(self.memory_bounds);
+        if let Some(ref device) = self.boot_device {
+            multiboot.set_has_boot_device(true);
+            multiboot.header.boot_device = device.clone();
+        }
+        if let Some(command_line) = self.command_line {
+            multiboot.set_has_cmdline(true);
+            multiboot.header.cmdline = cursor.string(command_line);
+        }
+        if let Some(name) = self.boot_loader_name {
+            multiboot.set_has_boot_loader_name(true);
+            multiboot.header.boot_loader_name = cursor.string(name);
+        }
+        if let Some(modules) = self.modules {
+            let (table, table_offset) = cursor.reserve(modules.len() * size_of::<MBModule>());
+            for index in 0..modules.len() {
+                let (start, end, string) = modules.get(index);
+                let entry = MBModule {
+                    start: start.try_into().ok()?,
+                    end: end.try_into().ok()?,
+                    string: string.map_or(0, |s| cursor.string(s)),
+                    reserved: 0,
+                };
+                cursor.write(table_offset + index * size_of::<MBModule>(), entry);
+            }
+            multiboot.set_has_modules(true);
+            multiboot.header.mods_addr = table;
+            multiboot.header.mods_count = modules.len().try_into().ok()?;
+        }
+        let symbols = match self.symbols {
+            Some(BuilderSymbols::Fixed(symbols)) => Some(symbols),
+            Some(BuilderSymbols::Elf {
+                headers,
+                num,
+                size,
+                shndx,
+            }) => Some(SymbolType::Elf(ElfSymbols {
+                num,
+                size,
+                addr: cursor.bytes(headers),
+                shndx,
+            })),
+            Some(BuilderSymbols::AOut {
+                tables,
+                tabsize,
+                strsize,
+            }) => Some(SymbolType::AOut(AOutSymbols {
+                tabsize,
+                strsize,
+                addr: cursor.bytes(tables),
+                reserved: 0,
+            })),
+            None => None,
+        };
+        multiboot.set_symbols(symbols);
+        if let Some(memory_map) = self.memory_map {
+            multiboot.set_has_memory_map(true);
+            multiboot.header.mmap_addr = cursor.bytes(memory_map);
+            multiboot.header.mmap_length = memory_map.len().try_into().ok()?;
+        }
+        if let Some(drives) = self.drives {
+            multiboot.set_has_drives(true);
+            multiboot.header.drives_addr = cursor.bytes(drives);
+            multiboot.header.drives_length = drives.len().try_into().ok()?;
+        }
+        if let Some(config_table) = self.config_table {
+            multiboot.set_has_config_table(true);
+            multiboot.header._config_table = config_table;
+        }
+        if let Some(apm_table) = self.apm_table {
+            multiboot.set_has_apm_table(true);
+            multiboot.header.apm_table = cursor.bytes(apm_table);
+        }
+        if let Some(vbe) = self.vbe {
+            multiboot.set_has_vbe(true);
+            let header = &mut multiboot.header;
+            header.vbe_control_info = cursor.bytes(vbe.control_info);
+            header.vbe_mode_info = cursor.bytes(vbe.mode_info);
+            header._vbe_mode = vbe.mode;
+            header._vbe_interface_seg = vbe.interface.0;
+            header._vbe_interface_off = vbe.interface.1;
+            header._vbe_interface_len = vbe.interface.2;
+        }
+        let mut framebuffer_table = self.framebuffer_table.clone();
+        if let (Some(table), Some((palette, num_colors))) = (framebuffer_table.as_mut(), palette) {
+            table.ty = 0;
+            table.color_info = ColorInfo {
+                palette: ColorInfoPalette {
+                    palette_addr: cursor.bytes(palette),
+                    palette_num_colors: num_colors,
+                },
+            };
+        }
+        multiboot.set_framebuffer_table(framebuffer_table);
+
+        cursor.write(info_offset, info);
+        Some(size)
+    }
+}
+
+/// Hands out consecutive parts of the buffer of a [`MultibootInfoBuilder`].
+///
+/// [`MultibootInfoBuilder`]: struct.MultibootInfoBuilder.html
+struct BuilderCursor<'b> {
+    buffer: &'b mut [u8],
+    base: PAddr,
+    offset: usize,
+}
+
+impl<'b> BuilderCursor<'b> {
+    /// Reserve `length` bytes, returns their address and offset.
+    fn reserve(&mut self, length: usize) -> (u32, usize) {
+        let offset = self.offset;
+        self.offset = round_up!(offset + length, BUILDER_ALIGNMENT);
+        // the builder checked that everything fits below 4 GiB
+        ((self.base + offset as PAddr) as u32, offset)
+    }
+
+    /// Copy `bytes` into the buffer.
+    fn bytes(&mut self, bytes: &[u8]) -> u32 {
+        let (addr, offset) = self.reserve(bytes.len());
+        self.buffer[offset..offset + bytes.len()].copy_from_slice(bytes);
+        addr
+    }
+
+    /// Copy `string` into the buffer and terminate it.
+    fn string(&mut self, string: &[u8]) -> u32 {
+        // the buffer is zeroed
+        let (addr, offset) = self.reserve(string.len() + 1);
+        self.buffer[offset..offset + string.len()].copy_from_slice(string);
+        addr
+    }
+
+    /// Write `value` at `offset`.
+    fn write<T>(&mut self, offset: usize, value: T) {
+        let dst = &mut self.buffer[offset..offset + size_of::<T>()];
+        unsafe { ptr::write_unaligned(dst.as_mut_ptr().cast::<T>(), value) };
+    }
+}
+
+/// Memory management for a [`Multiboot`] that only sets fields that aren't pointers.
+///
+/// [`Multiboot`]: struct.Multiboot.html
+struct NoMemory;
+
+impl PhysicalMemoryMapper for NoMemory {
+    unsafe fn paddr_to_slice(&self, _addr: PAddr, _length: usize) -> Option<&'static [u8]> {
+        None
+    }
+}
+
+impl MemoryAllocator for NoMemory {
+    unsafe fn allocate(&mut self, _length: usize) -> Option<(PAddr, &mut [u8])> {
+        None
+    }
+
+    unsafe fn deallocate(&mut self, _addr: PAddr) {}
+}
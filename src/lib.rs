@@ -17,12 +17,21 @@
 #![crate_name = "multiboot"]
 #![crate_type = "lib"]
 
+#[cfg(feature = "decompress")]
+extern crate miniz_oxide;
+
 macro_rules! round_up {
     ($num:expr, $s:expr) => {
         (($num + $s - 1) / $s) * $s
     };
 }
 
+macro_rules! round_down {
+    ($num:expr, $s:expr) => {
+        ($num / $s) * $s
+    };
+}
+
 macro_rules! flag {
     ($doc:meta, $fun:ident, $bit:expr) => {
         #[$doc]
@@ -45,8 +54,19 @@ macro_rules! flag {
     };
 }
 
+pub mod archive;
+#[cfg(feature = "boot_params")]
+pub mod boot_params;
+pub mod cmdline;
+#[cfg(feature = "decompress")]
+pub mod decompress;
+pub mod e820;
+pub mod frame_allocator;
 pub mod header;
 pub mod information;
+pub mod loader;
+pub mod module_kind;
+pub mod uefi;
 
 #[cfg(doctest)]
 mod test_readme {
@@ -0,0 +1,416 @@
+//! This module contains helpers for bootloaders that load a kernel and its modules.
+//!
+//! [`BootConfig::boot`] does everything at once: It copies the kernel to the
+//! addresses given by its [`LoadPlan`], places the modules and the Multiboot
+//! information in available memory below 4 GiB and returns the registers
+//! to jump to the kernel with.
+//!
+//! The pieces can also be used on their own: [`Multiboot::load_modules`] copies
+//! the contents of each module into newly allocated memory, honoring
+//! [`Header::wants_modules_page_aligned`], and publishes the module table.
+//! [`check_modules`] validates modules that were placed otherwise.
+//! [`PhysicalAllocator`] hands out memory from a memory map.
+//!
+//! [`BootConfig::boot`]: struct.BootConfig.html#method.boot
+//! [`LoadPlan`]: ../header/struct.LoadPlan.html
+//! [`Multiboot::load_modules`]: ../information/struct.Multiboot.html#method.load_modules
+//! [`Header::wants_modules_page_aligned`]: ../header/struct.Header.html#method.wants_modules_page_aligned
+//! [`check_modules`]: fn.check_modules.html
+//! [`PhysicalAllocator`]: struct.PhysicalAllocator.html
+
+use core::mem::size_of;
+use core::ops::Range;
+use core::ptr;
+
+use header::{Header, LoadPlan, Quirks};
+use information::{
+    AllocationPurpose, AllocationRequest, MemoryAllocator, MemoryEntry, MemoryType, Module,
+    Multiboot, MultibootInfo, PAddr, PhysicalMemoryMapper, SIGNATURE_EAX,
+};
+
+/// Alignment of modules if the kernel asks for page aligned modules.
+pub const MODULE_ALIGNMENT: PAddr = 4096;
+
+/// The contents and string of a module that is to be loaded.
+#[derive(Debug, Copy, Clone)]
+pub struct ModuleSource<'d> {
+    /// Contents of the module.
+    pub data: &'d [u8],
+    /// Name of the module, usually its path followed by arguments.
+    pub string: Option<&'d str>,
+}
+
+impl<'d> ModuleSource<'d> {
+    pub fn new(data: &'d [u8], string: Option<&'d str>) -> Self {
+        Self { data, string }
+    }
+}
+
+/// Reasons why modules can't be loaded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// Memory for the module with this index couldn't be allocated.
+    AllocationFailed(usize),
+    /// The module with this index doesn't fit below 4 GiB.
+    AddressTooLarge(usize),
+    /// The module with this index ends before it starts.
+    InvalidRange(usize),
+    /// The module with this index doesn't start on a page boundary.
+    Misaligned(usize),
+    /// The modules with these indices overlap.
+    Overlap(usize, usize),
+    /// The module with this index overlaps the kernel.
+    OverlapsKernel(usize),
+}
+
+/// Copy the contents of a module into newly allocated memory.
+///
+/// The memory is aligned to `align`, which has to be a power of two.
+/// `index` is only used for errors.
+pub fn copy_module<'d, M: MemoryAllocator + ?Sized>(
+    index: usize,
+    source: &ModuleSource<'d>,
+    align: PAddr,
+    memory_management: &mut M,
+) -> Result<Module<'d>, LoadError> {
+    let length = source.data.len();
+    let request = AllocationRequest::new(length, AllocationPurpose::Module).align(align as usize);
+    let (addr, buffer) = unsafe { memory_management.allocate_request(request) }
+        .ok_or(LoadError::AllocationFailed(index))?;
+    buffer[..length].copy_from_slice(source.data);
+    let module = Module::new(addr, addr + length as PAddr, source.string);
+    if module.end > u32::MAX.into() {
+        unsafe { memory_management.deallocate_sized(addr, length) };
+        return Err(LoadError::AddressTooLarge(index));
+    }
+    Ok(module)
+}
+
+/// Check that the modules are usable by the kernel.
+///
+/// Modules must end below 4 GiB, must not overlap each other or the kernel
+/// and, if `page_aligned` is set, must start at a multiple of [`MODULE_ALIGNMENT`].
+/// Empty modules don't overlap anything.
+///
+/// [`MODULE_ALIGNMENT`]: constant.MODULE_ALIGNMENT.html
+pub fn check_modules<'m, I>(
+    modules: I,
+    kernel: Range<PAddr>,
+    page_aligned: bool,
+) -> Result<(), LoadError>
+where
+    I: IntoIterator<Item = Module<'m>>,
+    I::IntoIter: Clone,
+{
+    let overlap = |a: &Range<PAddr>, b: &Range<PAddr>| {
+        a.start < a.end && b.start < b.end && a.start < b.end && b.start < a.end
+    };
+    let modules = modules.into_iter();
+    for (index, module) in modules.clone().enumerate() {
+        let range = module.start..module.end;
+        if module.start > module.end {
+            return Err(LoadError::InvalidRange(index));
+        }
+        if module.end > u32::MAX.into() {
+            return Err(LoadError::AddressTooLarge(index));
+        }
+        if page_aligned && module.start & (MODULE_ALIGNMENT - 1) != 0 {
+            return Err(LoadError::Misaligned(index));
+        }
+        if overlap(&range, &kernel) {
+            return Err(LoadError::OverlapsKernel(index));
+        }
+        let other = modules
+            .clone()
+            .take(index)
+            .position(|other| overlap(&range, &(other.start..other.end)));
+        if let Some(other) = other {
+            return Err(LoadError::Overlap(other, index));
+        }
+    }
+    Ok(())
+}
+
+/// Nothing is placed below this address, so no pointer ends up being 0.
+const LOWEST_ADDRESS: PAddr = 0x1000;
+/// Everything has to be placed below 4 GiB.
+const HIGHEST_ADDRESS: PAddr = 1 << 32;
+
+/// Check whether `range` lies within available memory below 4 GiB.
+fn is_available(memory_map: &[MemoryEntry], range: &Range<PAddr>) -> bool {
+    let entry_range = |entry: &MemoryEntry| {
+        entry.base_address()..entry.base_address().saturating_add(entry.length())
+    };
+    range.end <= HIGHEST_ADDRESS
+        && memory_map.iter().any(|entry| {
+            let entry_range = entry_range(entry);
+            entry.memory_type() == MemoryType::Available
+                && entry_range.start <= range.start
+                && range.end <= entry_range.end
+        })
+        && !memory_map.iter().any(|entry| {
+            let entry_range = entry_range(entry);
+            entry.memory_type() != MemoryType::Available
+                && entry_range.start < range.end
+                && range.start < entry_range.end
+        })
+}
+
+/// Allocates physical memory from the available regions of a memory map.
+///
+/// Memory is handed out from the bottom upwards and below 4 GiB only.
+/// It's never reused, so deallocating does nothing.
+/// `map` has to make the given physical range accessible.
+pub struct PhysicalAllocator<'m, F> {
+    memory_map: &'m [MemoryEntry],
+    reserved: &'m [Range<PAddr>],
+    /// Everything below this has been handed out (or skipped).
+    next: PAddr,
+    /// Modules are placed at or above this.
+    modules_start: PAddr,
+    map: F,
+}
+
+impl<'m, F> PhysicalAllocator<'m, F>
+where
+    F: Fn(PAddr, usize) -> Option<&'static mut [u8]>,
+{
+    /// Create an allocator for the available regions in `memory_map`.
+    ///
+    /// The `reserved` ranges, e.g. the kernel, are never handed out.
+    pub fn new(memory_map: &'m [MemoryEntry], reserved: &'m [Range<PAddr>], map: F) -> Self {
+        Self {
+            memory_map,
+            reserved,
+            next: LOWEST_ADDRESS,
+            modules_start: LOWEST_ADDRESS,
+            map,
+        }
+    }
+
+    /// Don't place modules below `addr`, other allocations aren't affected.
+    pub fn skip_modules_to(&mut self, addr: PAddr) {
+        self.modules_start = addr;
+    }
+
+    /// Find the lowest free range of `length` bytes aligned to `align` that
+    /// starts at or above `start` and ends below `max_address`.
+    fn find(&self, length: usize, align: PAddr, start: PAddr, max_address: PAddr) -> Option<PAddr> {
+        let length = length as PAddr;
+        let align_up = |addr: PAddr| {
+            addr.checked_add(align - 1)
+                .map(|addr| round_down!(addr, align))
+        };
+        let unavailable = self
+            .memory_map
+            .iter()
+            .filter(|entry| entry.memory_type() != MemoryType::Available)
+            .map(|entry| entry.base_address()..entry.base_address().saturating_add(entry.length()));
+        let mut best: Option<PAddr> = None;
+        for entry in self.memory_map {
+            if entry.memory_type() != MemoryType::Available {
+                continue;
+            }
+            let end = entry
+                .base_address()
+                .saturating_add(entry.length())
+                .min(HIGHEST_ADDRESS)
+                .min(max_address);
+            let mut candidate = match align_up(entry.base_address().max(start)) {
+                Some(candidate) => candidate,
+                None => continue,
+            };
+            while candidate.saturating_add(length) <= end {
+                // empty allocations still may not point into used memory
+                let used = candidate..candidate + length.max(1);
+                let conflict = self
+                    .reserved
+                    .iter()
+                    .cloned()
+                    .chain(unavailable.clone())
+                    .filter(|other| other.start < used.end && used.start < other.end)
+                    .map(|other| other.end)
+                    .max();
+                match conflict.map(align_up) {
+                    Some(Some(conflict_end)) => candidate = conflict_end,
+                    // the conflict reaches the end of the address space
+                    Some(None) => break,
+                    None => {
+                        best = Some(best.map_or(candidate, |best| best.min(candidate)));
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+impl<'m, F> PhysicalMemoryMapper for PhysicalAllocator<'m, F>
+where
+    F: Fn(PAddr, usize) -> Option<&'static mut [u8]>,
+{
+    unsafe fn paddr_to_slice(&self, addr: PAddr, length: usize) -> Option<&'static [u8]> {
+        (self.map)(addr, length).map(|slice| &*slice)
+    }
+}
+
+impl<'m, F> MemoryAllocator for PhysicalAllocator<'m, F>
+where
+    F: Fn(PAddr, usize) -> Option<&'static mut [u8]>,
+{
+    unsafe fn allocate(&mut self, length: usize) -> Option<(PAddr, &mut [u8])> {
+        self.allocate_aligned(length, 8)
+    }
+
+    unsafe fn allocate_aligned(
+        &mut self,
+        length: usize,
+        align: usize,
+    ) -> Option<(PAddr, &mut [u8])> {
+        self.allocate_request(AllocationRequest {
+            size: length,
+            align,
+            max_address: HIGHEST_ADDRESS,
+            purpose: AllocationPurpose::Module,
+        })
+    }
+
+    unsafe fn allocate_request(
+        &mut self,
+        request: AllocationRequest,
+    ) -> Option<(PAddr, &mut [u8])> {
+        let start = match request.purpose {
+            AllocationPurpose::Module => self.next.max(self.modules_start),
+            _ => self.next,
+        };
+        let addr = self.find(
+            request.size,
+            request.align as PAddr,
+            start,
+            request.max_address,
+        )?;
+        let slice = (self.map)(addr, request.size)?;
+        self.next = addr + request.size as PAddr;
+        Some((addr, slice))
+    }
+
+    unsafe fn deallocate(&mut self, _addr: PAddr) {}
+}
+
+/// Everything a bootloader needs to boot a kernel.
+#[derive(Debug, Copy, Clone)]
+pub struct BootConfig<'d> {
+    /// The Multiboot header of the kernel.
+    pub header: &'d Header,
+    /// How to load the kernel, usually [`LoadPlan::new`].
+    ///
+    /// [`LoadPlan::new`]: ../header/struct.LoadPlan.html#method.new
+    pub load_plan: &'d LoadPlan,
+    /// The kernel image.
+    pub image: &'d [u8],
+    /// The modules to pass to the kernel.
+    pub modules: &'d [ModuleSource<'d>],
+    /// The command line of the kernel, usually starting with its path.
+    pub command_line: Option<&'d str>,
+    /// The name of the bootloader.
+    pub boot_loader_name: Option<&'d str>,
+    /// The memory map, which is also passed to the kernel.
+    pub memory_map: &'d [MemoryEntry],
+    /// GRUB quirks to emulate.
+    ///
+    /// The load plan has to be created with the same quirks,
+    /// see [`LoadPlan::with_quirks`].
+    ///
+    /// [`LoadPlan::with_quirks`]: ../header/struct.LoadPlan.html#method.with_quirks
+    pub quirks: Quirks,
+}
+
+/// The registers to jump to the entry point of the kernel with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BootRegisters {
+    /// Always [`SIGNATURE_EAX`](../information/constant.SIGNATURE_EAX.html).
+    pub eax: u32,
+    /// The physical address of the Multiboot information.
+    pub ebx: u32,
+    /// The physical address of the entry point.
+    pub entry: u32,
+}
+
+/// Reasons why a kernel can't be booted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BootError {
+    /// The load plan refers to data outside of the image.
+    InvalidImage,
+    /// The kernel has to be loaded to memory that isn't available or above 4 GiB.
+    KernelNotInMemory,
+    /// The modules couldn't be loaded.
+    Modules(LoadError),
+    /// There is no memory left for the Multiboot information.
+    OutOfMemory,
+}
+
+impl<'d> BootConfig<'d> {
+    /// Load the kernel and the modules and build the Multiboot information.
+    ///
+    /// The kernel is copied to the addresses of its load plan, everything else
+    /// is placed in available memory that doesn't overlap the kernel.
+    /// `map` has to make the given physical range accessible.
+    ///
+    /// # Safety
+    /// The available memory in the memory map must actually be unused and
+    /// `map` must return slices that refer to the given physical memory.
+    pub unsafe fn boot<F>(&self, map: F) -> Result<BootRegisters, BootError>
+    where
+        F: Fn(PAddr, usize) -> Option<&'static mut [u8]>,
+    {
+        let kernel = self.load_plan.range();
+        for segment in self.load_plan.segments() {
+            let range = segment.range();
+            if !is_available(self.memory_map, &range) {
+                return Err(BootError::KernelNotInMemory);
+            }
+            let offset = segment.offset as usize;
+            let data = self
+                .image
+                .get(offset..offset + segment.file_size as usize)
+                .ok_or(BootError::InvalidImage)?;
+            let memory = map(range.start, segment.memory_size as usize)
+                .ok_or(BootError::KernelNotInMemory)?;
+            let (file, bss) = memory.split_at_mut(data.len());
+            file.copy_from_slice(data);
+            bss.iter_mut().for_each(|b| *b = 0);
+        }
+
+        let reserved = [kernel.clone()];
+        let mut allocator = PhysicalAllocator::new(self.memory_map, &reserved, map);
+        if self.quirks.modules_after_kernel {
+            allocator.skip_modules_to(kernel.end);
+        }
+        let info_addr = {
+            let request =
+                AllocationRequest::new(size_of::<MultibootInfo>(), AllocationPurpose::Info)
+                    .align(8);
+            let (addr, info) = allocator
+                .allocate_request(request)
+                .ok_or(BootError::OutOfMemory)?;
+            ptr::write_unaligned(info.as_mut_ptr().cast(), MultibootInfo::default());
+            addr
+        };
+        let mut multiboot =
+            Multiboot::from_ptr(info_addr, &mut allocator).ok_or(BootError::OutOfMemory)?;
+        multiboot
+            .try_set_command_line(self.command_line)
+            .and_then(|()| multiboot.try_set_boot_loader_name(self.boot_loader_name))
+            .and_then(|()| multiboot.try_set_memory_map_and_bounds(Some(self.memory_map)))
+            .map_err(|_| BootError::OutOfMemory)?;
+        multiboot
+            .load_modules(self.header, kernel, self.modules)
+            .map_err(BootError::Modules)?;
+        Ok(BootRegisters {
+            eax: SIGNATURE_EAX,
+            ebx: info_addr as u32,
+            entry: self.load_plan.entry(),
+        })
+    }
+}
@@ -0,0 +1,123 @@
+//! This module detects the type of a module from its first bytes.
+//!
+//! Use [`Module::detect_kind`] or [`ModuleKind::detect`].
+//!
+//! [`Module::detect_kind`]: ../information/struct.Module.html#method.detect_kind
+//! [`ModuleKind::detect`]: enum.ModuleKind.html#method.detect
+
+use core::convert::TryInto;
+
+use archive::{Archive, Format};
+
+/// [`ModuleKind::detect`] never looks past this many bytes.
+///
+/// [`ModuleKind::detect`]: enum.ModuleKind.html#method.detect
+pub const DETECT_LENGTH: usize = 512;
+
+/// `e_machine` of x86.
+pub const EM_386: u16 = 3;
+/// `e_machine` of ARM.
+pub const EM_ARM: u16 = 40;
+/// `e_machine` of x86-64.
+pub const EM_X86_64: u16 = 62;
+/// `e_machine` of AArch64.
+pub const EM_AARCH64: u16 = 183;
+/// `e_machine` of RISC-V.
+pub const EM_RISCV: u16 = 243;
+
+/// Size of the addresses in an ELF file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ElfClass {
+    /// 32 bit addresses, `ELFCLASS32`.
+    Elf32,
+    /// 64 bit addresses, `ELFCLASS64`.
+    Elf64,
+}
+
+/// Types of modules that can be recognized.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ModuleKind {
+    /// An ELF file for the given machine, e.g. [`EM_386`](constant.EM_386.html).
+    Elf { class: ElfClass, machine: u16 },
+    /// gzip compressed data.
+    Gzip,
+    /// xz compressed data.
+    Xz,
+    /// Zstandard compressed data.
+    Zstd,
+    /// A cpio archive in any format.
+    Cpio,
+    /// A ustar archive.
+    Tar,
+    /// A PC Screen Font, version 1 or 2.
+    Psf { version: u8 },
+    /// A Windows bitmap.
+    Bmp,
+    /// None of the above.
+    Unknown,
+}
+
+impl ModuleKind {
+    /// Inspect the first bytes of `data`.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"\x7fELF") {
+            if let Some(kind) = detect_elf(data) {
+                return kind;
+            }
+        } else if data.starts_with(&[0x1f, 0x8b]) {
+            return ModuleKind::Gzip;
+        } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            return ModuleKind::Xz;
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            return ModuleKind::Zstd;
+        } else if data.starts_with(&[0x36, 0x04]) {
+            return ModuleKind::Psf { version: 1 };
+        } else if data.starts_with(&[0x72, 0xb5, 0x4a, 0x86]) {
+            return ModuleKind::Psf { version: 2 };
+        } else if data.starts_with(b"BM") && is_bmp(data) {
+            return ModuleKind::Bmp;
+        }
+        // odc and the binary format are recognized, but not parsed
+        if data.starts_with(b"070707")
+            || data.starts_with(&[0xc7, 0x71])
+            || data.starts_with(&[0x71, 0xc7])
+        {
+            return ModuleKind::Cpio;
+        }
+        match Archive::new(data).map(|archive| archive.format()) {
+            Some(Format::Cpio) => ModuleKind::Cpio,
+            Some(Format::Tar) => ModuleKind::Tar,
+            None => ModuleKind::Unknown,
+        }
+    }
+
+    /// Is this compressed data?
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, ModuleKind::Gzip | ModuleKind::Xz | ModuleKind::Zstd)
+    }
+}
+
+fn detect_elf(data: &[u8]) -> Option<ModuleKind> {
+    let class = match data.get(4)? {
+        1 => ElfClass::Elf32,
+        2 => ElfClass::Elf64,
+        _ => return None,
+    };
+    let machine = data.get(18..20)?.try_into().unwrap();
+    let machine = match data.get(5)? {
+        1 => u16::from_le_bytes(machine),
+        2 => u16::from_be_bytes(machine),
+        _ => return None,
+    };
+    Some(ModuleKind::Elf { class, machine })
+}
+
+/// Check the size of the DIB header, "BM" alone is too likely.
+fn is_bmp(data: &[u8]) -> bool {
+    match data.get(14..18) {
+        Some(size) => {
+            [12, 40, 52, 56, 64, 108, 124].contains(&u32::from_le_bytes(size.try_into().unwrap()))
+        }
+        None => false,
+    }
+}
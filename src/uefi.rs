@@ -0,0 +1,246 @@
+//! This module converts UEFI memory maps into Multiboot memory maps.
+//!
+//! A UEFI application that boots Multiboot kernels gets the memory map from
+//! `GetMemoryMap` as an array of descriptors. The descriptors are at least
+//! 40 bytes long, but firmware may use a larger stride, which is why
+//! [`MemoryDescriptorIter`] takes the descriptor size that `GetMemoryMap` returned.
+//!
+//! The conversion follows GRUB: the descriptor types are mapped to Multiboot
+//! types, sorted by address and adjacent regions of the same type are merged.
+//!
+//! ```rust
+//! use multiboot::information::MemoryEntry;
+//! use multiboot::uefi::{self, MemoryDescriptorIter};
+//! # let buffer = [0u8; 96];
+//! # let descriptor_size = 48;
+//! let descriptors = MemoryDescriptorIter::new(&buffer, descriptor_size).unwrap();
+//! let mut entries = [MemoryEntry::default(); 16];
+//! let count = uefi::convert_memory_map(descriptors, &mut entries).unwrap();
+//! // now publish `entries[..count]` using `Multiboot::set_memory_regions`
+//! # assert_eq!(count, 0);
+//! ```
+//!
+//! [`MemoryDescriptorIter`]: struct.MemoryDescriptorIter.html
+
+use core::convert::TryInto;
+use core::ops::Range;
+
+use information::{MemoryEntry, MemoryType, PAddr};
+
+/// Size of the pages the number of pages in a descriptor refers to.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Minimum size of a memory descriptor.
+pub const DESCRIPTOR_SIZE: usize = 40;
+
+/// `EfiReservedMemoryType`
+pub const EFI_RESERVED_MEMORY_TYPE: u32 = 0;
+/// `EfiLoaderCode`
+pub const EFI_LOADER_CODE: u32 = 1;
+/// `EfiLoaderData`
+pub const EFI_LOADER_DATA: u32 = 2;
+/// `EfiBootServicesCode`
+pub const EFI_BOOT_SERVICES_CODE: u32 = 3;
+/// `EfiBootServicesData`
+pub const EFI_BOOT_SERVICES_DATA: u32 = 4;
+/// `EfiRuntimeServicesCode`
+pub const EFI_RUNTIME_SERVICES_CODE: u32 = 5;
+/// `EfiRuntimeServicesData`
+pub const EFI_RUNTIME_SERVICES_DATA: u32 = 6;
+/// `EfiConventionalMemory`
+pub const EFI_CONVENTIONAL_MEMORY: u32 = 7;
+/// `EfiUnusableMemory`
+pub const EFI_UNUSABLE_MEMORY: u32 = 8;
+/// `EfiACPIReclaimMemory`
+pub const EFI_ACPI_RECLAIM_MEMORY: u32 = 9;
+/// `EfiACPIMemoryNVS`
+pub const EFI_ACPI_MEMORY_NVS: u32 = 10;
+/// `EfiMemoryMappedIO`
+pub const EFI_MEMORY_MAPPED_IO: u32 = 11;
+/// `EfiMemoryMappedIOPortSpace`
+pub const EFI_MEMORY_MAPPED_IO_PORT_SPACE: u32 = 12;
+/// `EfiPalCode`
+pub const EFI_PAL_CODE: u32 = 13;
+/// `EfiPersistentMemory`
+pub const EFI_PERSISTENT_MEMORY: u32 = 14;
+
+/// Attribute: The memory supports being configured as write-back cacheable.
+pub const EFI_MEMORY_WB: u64 = 0x8;
+/// Attribute: The memory is non-volatile.
+pub const EFI_MEMORY_NV: u64 = 0x8000;
+/// Attribute: The memory has to be mapped by the OS for runtime services.
+pub const EFI_MEMORY_RUNTIME: u64 = 0x8000_0000_0000_0000;
+
+/// A UEFI memory descriptor (`EFI_MEMORY_DESCRIPTOR`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryDescriptor {
+    /// One of the `EFI_*` memory types.
+    pub ty: u32,
+    /// Start of the region, aligned to 4 KiB.
+    pub physical_start: u64,
+    /// Where runtime services want the region to be mapped.
+    pub virtual_start: u64,
+    /// Length of the region in 4 KiB pages.
+    pub number_of_pages: u64,
+    /// A combination of the `EFI_MEMORY_*` attributes.
+    pub attribute: u64,
+}
+
+impl MemoryDescriptor {
+    /// Parse a descriptor from the start of `bytes`.
+    ///
+    /// Returns `None` if `bytes` is shorter than 40 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < DESCRIPTOR_SIZE {
+            return None;
+        }
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        // there are 4 bytes of padding after the type
+        Some(Self {
+            ty: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            physical_start: u64_at(8),
+            virtual_start: u64_at(16),
+            number_of_pages: u64_at(24),
+            attribute: u64_at(32),
+        })
+    }
+
+    /// Get the physical memory this descriptor covers.
+    pub fn range(&self) -> Range<PAddr> {
+        let length = self.number_of_pages.saturating_mul(PAGE_SIZE);
+        self.physical_start..self.physical_start.saturating_add(length)
+    }
+
+    /// Get the Multiboot type of this descriptor.
+    ///
+    /// Memory used by the loader and the boot services is available to the kernel,
+    /// since boot services have been exited by the time it runs.
+    /// Unknown types are reserved.
+    pub fn memory_type(&self) -> MemoryType {
+        match self.ty {
+            EFI_LOADER_CODE
+            | EFI_LOADER_DATA
+            | EFI_BOOT_SERVICES_CODE
+            | EFI_BOOT_SERVICES_DATA
+            | EFI_CONVENTIONAL_MEMORY => MemoryType::Available,
+            EFI_UNUSABLE_MEMORY => MemoryType::Defect,
+            EFI_ACPI_RECLAIM_MEMORY => MemoryType::ACPI,
+            EFI_ACPI_MEMORY_NVS => MemoryType::NVS,
+            EFI_PERSISTENT_MEMORY => MemoryType::Persistent,
+            _ => MemoryType::Reserved,
+        }
+    }
+}
+
+/// Used to iterate over the descriptors returned by `GetMemoryMap`.
+#[derive(Debug, Clone)]
+pub struct MemoryDescriptorIter<'a> {
+    bytes: &'a [u8],
+    descriptor_size: usize,
+}
+
+impl<'a> MemoryDescriptorIter<'a> {
+    /// Iterate over the descriptors in `bytes` that are each `descriptor_size` bytes apart.
+    ///
+    /// `bytes` should be the part of the buffer that `GetMemoryMap` filled.
+    /// Returns `None` if `descriptor_size` is smaller than 40 bytes.
+    pub fn new(bytes: &'a [u8], descriptor_size: usize) -> Option<Self> {
+        if descriptor_size < DESCRIPTOR_SIZE {
+            return None;
+        }
+        Some(Self {
+            bytes,
+            descriptor_size,
+        })
+    }
+}
+
+impl<'a> Iterator for MemoryDescriptorIter<'a> {
+    type Item = MemoryDescriptor;
+
+    fn next(&mut self) -> Option<MemoryDescriptor> {
+        if self.bytes.len() < DESCRIPTOR_SIZE {
+            return None;
+        }
+        let descriptor = MemoryDescriptor::from_bytes(self.bytes);
+        self.bytes = self.bytes.get(self.descriptor_size..).unwrap_or(&[]);
+        descriptor
+    }
+}
+
+/// Convert UEFI memory descriptors into Multiboot memory entries.
+///
+/// The entries are written to `entries`, sorted by address.
+/// Regions of the same type that touch or overlap are merged into one entry
+/// and empty descriptors are skipped, so `entries` only needs to be as large
+/// as the resulting map.
+///
+/// Returns the number of entries written or `None` if `entries` is too small.
+pub fn convert_memory_map<I: IntoIterator<Item = MemoryDescriptor>>(
+    descriptors: I,
+    entries: &mut [MemoryEntry],
+) -> Option<usize> {
+    let mut count = 0;
+    for descriptor in descriptors {
+        let range = descriptor.range();
+        if range.start == range.end {
+            continue;
+        }
+        let entry = MemoryEntry::new(
+            range.start,
+            range.end - range.start,
+            descriptor.memory_type(),
+        );
+        // keep them sorted, firmware usually does so already
+        let mut index = entries[..count]
+            .iter()
+            .rposition(|e| e.base_address() <= range.start)
+            .map_or(0, |index| index + 1);
+        match index
+            .checked_sub(1)
+            .and_then(|i| merge(&entries[i], &entry))
+        {
+            Some(merged) => {
+                index -= 1;
+                entries[index] = merged;
+            }
+            None => {
+                if count == entries.len() {
+                    return None;
+                }
+                entries.copy_within(index..count, index + 1);
+                entries[index] = entry;
+                count += 1;
+            }
+        }
+        // the new entry may now touch the following ones
+        while index + 1 < count {
+            match merge(&entries[index], &entries[index + 1]) {
+                Some(merged) => {
+                    entries[index] = merged;
+                    entries.copy_within(index + 2..count, index + 1);
+                    count -= 1;
+                }
+                None => break,
+            }
+        }
+    }
+    Some(count)
+}
+
+/// Merge `next` into `entry` if they have the same type and touch or overlap.
+///
+/// `next` must not start before `entry`.
+fn merge(entry: &MemoryEntry, next: &MemoryEntry) -> Option<MemoryEntry> {
+    let end = entry.base_address() + entry.length();
+    if entry.memory_type() != next.memory_type() || next.base_address() > end {
+        return None;
+    }
+    let end = end.max(next.base_address() + next.length());
+    Some(MemoryEntry::new(
+        entry.base_address(),
+        end - entry.base_address(),
+        entry.memory_type(),
+    ))
+}
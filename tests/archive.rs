@@ -0,0 +1,167 @@
+extern crate multiboot;
+
+use multiboot::archive::{Archive, CpioIter, Format, TarIter, S_IFDIR, S_IFLNK, S_IFREG};
+
+/// Append a cpio "newc" entry.
+fn cpio_entry(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+    archive.extend_from_slice(b"070701");
+    let fields = [
+        1,                     // ino
+        mode,                  // mode
+        0,                     // uid
+        0,                     // gid
+        1,                     // nlink
+        0,                     // mtime
+        data.len() as u32,     // filesize
+        0,                     // devmajor
+        0,                     // devminor
+        0,                     // rdevmajor
+        0,                     // rdevminor
+        name.len() as u32 + 1, // namesize
+        0,                     // check
+    ];
+    for field in fields.iter() {
+        archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    archive.extend_from_slice(name.as_bytes());
+    archive.push(0);
+    archive.resize(archive.len().div_ceil(4) * 4, 0);
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().div_ceil(4) * 4, 0);
+}
+
+/// Append a ustar entry.
+fn tar_entry(archive: &mut Vec<u8>, prefix: &str, name: &str, ty: u8, mode: u32, data: &[u8]) {
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(format!("{:07o}\0", mode).as_bytes());
+    header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+    header[156] = ty;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    if ty == b'2' {
+        header[157..157 + data.len()].copy_from_slice(data);
+        header[124..136].copy_from_slice(b"00000000000\0");
+    }
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    archive.extend_from_slice(&header);
+    if ty != b'2' {
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().div_ceil(512) * 512, 0);
+    }
+}
+
+#[test]
+/// Iterate over a cpio archive and look up paths.
+fn cpio() {
+    let mut archive = Vec::new();
+    cpio_entry(&mut archive, ".", S_IFDIR | 0o755, b"");
+    cpio_entry(&mut archive, "bin", S_IFDIR | 0o755, b"");
+    cpio_entry(&mut archive, "bin/sh", S_IFREG | 0o755, b"#!shell");
+    cpio_entry(&mut archive, "init", S_IFLNK | 0o777, b"bin/sh");
+    cpio_entry(&mut archive, "TRAILER!!!", 0, b"");
+    // padding after the trailer is ignored
+    archive.extend_from_slice(&[0; 512]);
+
+    let entries: Vec<_> = CpioIter::new(&archive)
+        .map(|entry| (entry.name, entry.mode, entry.size()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (".", S_IFDIR | 0o755, 0),
+            ("bin", S_IFDIR | 0o755, 0),
+            ("bin/sh", S_IFREG | 0o755, 7),
+            ("init", S_IFLNK | 0o777, 6),
+        ]
+    );
+
+    let archive = Archive::new(&archive).unwrap();
+    assert_eq!(archive.format(), Format::Cpio);
+    let sh = archive.get("/bin/sh").unwrap();
+    assert!(sh.is_file());
+    assert_eq!(sh.data, b"#!shell");
+    assert!(archive.get("./bin/").unwrap().is_dir());
+    assert_eq!(archive.get("init").unwrap().data, b"bin/sh");
+    assert!(archive.get("init").unwrap().is_symlink());
+    assert!(archive.get("/").unwrap().is_dir());
+    assert!(archive.get("sh").is_none());
+    assert!(archive.get("TRAILER!!!").is_none());
+}
+
+#[test]
+/// A truncated cpio archive ends at the broken entry.
+fn cpio_truncated() {
+    let mut archive = Vec::new();
+    cpio_entry(&mut archive, "a", S_IFREG | 0o644, b"first");
+    cpio_entry(&mut archive, "b", S_IFREG | 0o644, b"second");
+    archive.truncate(archive.len() - 4);
+    let names: Vec<_> = CpioIter::new(&archive).map(|entry| entry.name).collect();
+    assert_eq!(names, vec!["a"]);
+    assert!(Archive::new(b"garbage").is_none());
+}
+
+#[test]
+/// Iterate over a ustar archive and look up paths.
+fn tar() {
+    let mut archive = Vec::new();
+    tar_entry(&mut archive, "", "etc/", b'5', 0o755, b"");
+    tar_entry(
+        &mut archive,
+        "",
+        "etc/hostname",
+        b'0',
+        0o644,
+        b"multiboot\n",
+    );
+    let long = [b'x'; 600];
+    tar_entry(
+        &mut archive,
+        "usr/share/very/long",
+        "file",
+        b'0',
+        0o600,
+        &long,
+    );
+    tar_entry(&mut archive, "", "sh", b'2', 0o777, b"bin/busybox");
+    archive.extend_from_slice(&[0; 1024]);
+
+    let entries: Vec<_> = TarIter::new(&archive)
+        .map(|entry| (entry.prefix, entry.name, entry.mode, entry.size()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            ("", "etc/", S_IFDIR | 0o755, 0),
+            ("", "etc/hostname", S_IFREG | 0o644, 10),
+            ("usr/share/very/long", "file", S_IFREG | 0o600, 600),
+            ("", "sh", S_IFLNK | 0o777, 11),
+        ]
+    );
+
+    let archive = Archive::new(&archive).unwrap();
+    assert_eq!(archive.format(), Format::Tar);
+    assert_eq!(archive.get("/etc/hostname").unwrap().data, b"multiboot\n");
+    assert!(archive.get("etc").unwrap().is_dir());
+    assert_eq!(
+        archive.get("usr/share/very/long/file").unwrap().data,
+        &long[..]
+    );
+    assert!(archive.get("usr/share/very/longfile").is_none());
+    assert!(archive.get("file").is_none());
+    assert_eq!(archive.get("sh").unwrap().data, b"bin/busybox");
+}
+
+#[test]
+/// Entries with a wrong checksum end the archive.
+fn tar_checksum() {
+    let mut archive = Vec::new();
+    tar_entry(&mut archive, "", "a", b'0', 0o644, b"a");
+    tar_entry(&mut archive, "", "b", b'0', 0o644, b"b");
+    archive[512 + 512] = b'c';
+    let names: Vec<_> = TarIter::new(&archive).map(|entry| entry.name).collect();
+    assert_eq!(names, vec!["a"]);
+}
@@ -0,0 +1,381 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::Mem;
+use multiboot::header::{Header, LoadPlan, LoadSegment, Quirks};
+use multiboot::information::{
+    AllocationPurpose, AllocationRequest, MemoryAllocator, MemoryEntry, MemoryType, Multiboot,
+    PAddr, SIGNATURE_EAX,
+};
+use multiboot::loader::{BootConfig, BootError, ModuleSource, PhysicalAllocator};
+
+/// Get 4 MiB of memory filled with garbage.
+fn machine() -> Mem {
+    let mem = Mem::new(0x400000, 0);
+    for byte in mem.memory.iter_mut() {
+        *byte = 0xaa;
+    }
+    mem
+}
+
+fn memory_map() -> [MemoryEntry; 3] {
+    [
+        MemoryEntry::new(0, 0x9f000, MemoryType::Available),
+        MemoryEntry::new(0x9f000, 0x61000, MemoryType::Reserved),
+        MemoryEntry::new(0x100000, 0x300000, MemoryType::Available),
+    ]
+}
+
+/// Build a 32-bit ELF with one segment per `(vaddr, paddr, data, memory_size)`.
+fn elf(entry: u32, segments: &[(u32, u32, &[u8], u32)]) -> Vec<u8> {
+    let mut image = vec![0; 52 + 32 * segments.len()];
+    image[0..6].copy_from_slice(b"\x7fELF\x01\x01");
+    image[16..18].copy_from_slice(&2u16.to_le_bytes());
+    image[18..20].copy_from_slice(&3u16.to_le_bytes());
+    image[24..28].copy_from_slice(&entry.to_le_bytes());
+    image[28..32].copy_from_slice(&52u32.to_le_bytes());
+    image[42..44].copy_from_slice(&32u16.to_le_bytes());
+    image[44..46].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+    for (index, &(vaddr, paddr, data, memory_size)) in segments.iter().enumerate() {
+        let offset = image.len() as u32;
+        let fields = [
+            1,
+            offset,
+            vaddr,
+            paddr,
+            data.len() as u32,
+            memory_size,
+            7,
+            4,
+        ];
+        let ph = 52 + 32 * index;
+        for (field, value) in fields.iter().enumerate() {
+            image[ph + field * 4..ph + field * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        image.extend_from_slice(data);
+    }
+    image
+}
+
+/// Put a Multiboot header with the given flags (and addresses) at `offset`.
+fn with_header(mut image: Vec<u8>, offset: usize, flags: u32, addresses: [u32; 5]) -> Vec<u8> {
+    if image.len() < offset + 32 {
+        image.resize(offset + 32, 0);
+    }
+    let checksum = 0u32.wrapping_sub(0x1BADB002).wrapping_sub(flags);
+    let fields = [0x1BADB002, flags, checksum];
+    for (index, value) in fields.iter().chain(addresses.iter()).enumerate() {
+        let field = offset + index * 4;
+        image[field..field + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    image
+}
+
+/// Append a Multiboot header without addresses to an ELF.
+fn with_elf_header(image: Vec<u8>, flags: u32) -> Vec<u8> {
+    let offset = image.len().div_ceil(4) * 4;
+    with_header(image, offset, flags, [0; 5])
+}
+
+#[test]
+/// Parse the program headers of an ELF.
+fn elf_plan() {
+    let image = elf(
+        0xc010_0010,
+        &[
+            (0xc010_0000, 0x100000, b"text", 0x1000),
+            (0xc020_0000, 0x200000, b"data", 0x2000),
+        ],
+    );
+    let plan = LoadPlan::from_elf(&image).unwrap();
+    assert_eq!(plan.entry(), 0x100010);
+    assert_eq!(plan.range(), 0x100000..0x202000);
+    assert_eq!(
+        plan.segments()[1],
+        LoadSegment {
+            offset: 52 + 64 + 4,
+            file_size: 4,
+            address: 0x200000,
+            memory_size: 0x2000,
+        }
+    );
+    let mut truncated = image.clone();
+    truncated.pop();
+    assert!(LoadPlan::from_elf(&truncated).is_none());
+    assert!(LoadPlan::from_elf(&image[..40]).is_none());
+}
+
+#[test]
+/// Parse the address fields of the header.
+fn kludge_plan() {
+    // the image starts at 0x100000, the header is at 0x100040
+    let image = with_header(
+        vec![0; 0x200],
+        0x40,
+        1 << 16,
+        [0x100040, 0x100000, 0, 0x101000, 0x100080],
+    );
+    let header = Header::from_slice(&image).unwrap();
+    let plan = LoadPlan::new(&header, &image).unwrap();
+    assert_eq!(plan.entry(), 0x100080);
+    assert_eq!(
+        plan.segments(),
+        &[LoadSegment {
+            offset: 0,
+            file_size: 0x200,
+            address: 0x100000,
+            memory_size: 0x1000,
+        }]
+    );
+
+    let image = with_header(
+        vec![0; 0x200],
+        0x40,
+        1 << 16,
+        [0x100040, 0x100000, 0x100400, 0, 0],
+    );
+    let header = Header::from_slice(&image).unwrap();
+    assert!(LoadPlan::new(&header, &image).is_none());
+}
+
+#[test]
+/// Boot an ELF with page aligned modules.
+fn boot_elf() {
+    let mut mem = machine();
+    let image = elf(
+        0xc010_0010,
+        &[(0xc010_0000, 0x100000, b"kernel code", 0x3000)],
+    );
+    let image = with_elf_header(image, 1 | 2);
+    let header = Header::from_slice(&image).unwrap();
+    let plan = LoadPlan::new(&header, &image).unwrap();
+    let memory_map = memory_map();
+    let modules = [
+        ModuleSource::new(b"initrd", Some("/boot/initrd")),
+        ModuleSource::new(&[0x55; 0x2345], Some("/boot/big")),
+    ];
+    let config = BootConfig {
+        header: &header,
+        load_plan: &plan,
+        image: &image,
+        modules: &modules,
+        command_line: Some("/boot/kernel console=ttyS0"),
+        boot_loader_name: Some("test"),
+        memory_map: &memory_map,
+        quirks: Quirks::default(),
+    };
+    let registers = unsafe { config.boot(mem.mapper()).unwrap() };
+    assert_eq!(registers.eax, SIGNATURE_EAX);
+    assert_eq!(registers.entry, 0x100010);
+
+    // the kernel is loaded and the bss is cleared
+    let kernel = mem.map(0x100000, 0x3000);
+    assert!(kernel.starts_with(b"kernel code"));
+    assert!(kernel[11..].iter().all(|&b| b == 0));
+
+    let multiboot = unsafe { Multiboot::from_ptr(registers.ebx.into(), &mut mem).unwrap() };
+    assert_eq!(multiboot.command_line(), Some("/boot/kernel console=ttyS0"));
+    assert_eq!(multiboot.boot_loader_name(), Some("test"));
+    assert_eq!(multiboot.lower_memory_bound(), Some(636));
+    assert_eq!(multiboot.memory_regions().unwrap().count(), 3);
+    let mut end = 0;
+    for (module, source) in multiboot.modules().unwrap().zip(modules.iter()) {
+        assert_eq!(module.start % 4096, 0);
+        assert!(module.start >= end);
+        assert!(module.end <= 0x100000 || module.start >= 0x103000);
+        assert_eq!(module.string, source.string);
+        assert_eq!(multiboot.module_data(&module), Some(source.data));
+        end = module.end;
+    }
+    for region in multiboot.occupied_regions() {
+        assert!(region.range.start >= 0x1000);
+        assert!(region.range.end <= 0x9f000 || region.range.start >= 0x103000);
+    }
+}
+
+#[test]
+/// Boot an a.out kludge kernel.
+fn boot_kludge() {
+    let mut mem = machine();
+    let mut image = with_header(
+        vec![0; 0x100],
+        0x40,
+        1 << 16,
+        [0x200040, 0x200000, 0, 0x202000, 0x200080],
+    );
+    image[0x80] = 0xf4;
+    let header = Header::from_slice(&image).unwrap();
+    let plan = LoadPlan::new(&header, &image).unwrap();
+    let memory_map = memory_map();
+    let config = BootConfig {
+        header: &header,
+        load_plan: &plan,
+        image: &image,
+        modules: &[],
+        command_line: None,
+        boot_loader_name: None,
+        memory_map: &memory_map,
+        quirks: Quirks::default(),
+    };
+    let registers = unsafe { config.boot(mem.mapper()).unwrap() };
+    assert_eq!(registers.entry, 0x200080);
+    assert_eq!(mem.map(0x200080, 1), [0xf4]);
+    assert!(mem.map(0x200100, 0x1f00).iter().all(|&b| b == 0));
+    let multiboot = unsafe { Multiboot::from_ptr(registers.ebx.into(), &mut mem).unwrap() };
+    assert!(multiboot.modules().is_none());
+    assert!(multiboot.command_line().is_none());
+}
+
+#[test]
+/// The kernel has to be loaded to available memory.
+fn kernel_not_in_memory() {
+    let mem = machine();
+    let memory_map = memory_map();
+    for &address in [0xa0000, 0x3ff000].iter() {
+        let image = elf(address, &[(address, address, b"kernel", 0x2000)]);
+        let image = with_elf_header(image, 0);
+        let header = Header::from_slice(&image).unwrap();
+        let plan = LoadPlan::new(&header, &image).unwrap();
+        let config = BootConfig {
+            header: &header,
+            load_plan: &plan,
+            image: &image,
+            modules: &[],
+            command_line: None,
+            boot_loader_name: None,
+            memory_map: &memory_map,
+            quirks: Quirks::default(),
+        };
+        assert_eq!(
+            unsafe { config.boot(mem.mapper()) },
+            Err(BootError::KernelNotInMemory)
+        );
+    }
+}
+
+#[test]
+/// Ignore the address fields of ELF images.
+fn bad_kludge() {
+    let image = elf(0x100000, &[(0x100000, 0x100000, b"kernel", 0x1000)]);
+    let offset = image.len().div_ceil(4) * 4;
+    // the address fields are nonsense
+    let image = with_header(image, offset, 1 << 16, [0x300000, 0x300000, 0, 0, 0x300000]);
+    let header = Header::from_slice(&image).unwrap();
+    let plan = LoadPlan::new(&header, &image).unwrap();
+    assert_eq!(plan.entry(), 0x300000);
+    let quirks = Quirks {
+        bad_kludge: true,
+        ..Quirks::default()
+    };
+    let plan = LoadPlan::with_quirks(&header, &image, quirks).unwrap();
+    assert_eq!(plan.entry(), 0x100000);
+    assert_eq!(plan.range(), 0x100000..0x101000);
+
+    // ELFs that can't be loaded from their program headers use the address fields
+    let mut elf64 = image.clone();
+    elf64[4] = 2;
+    let header = Header::from_slice(&elf64).unwrap();
+    let plan = LoadPlan::with_quirks(&header, &elf64, quirks).unwrap();
+    assert_eq!(plan.entry(), 0x300000);
+
+    // images that aren't ELFs still use the address fields
+    let image = with_header(
+        vec![0; 0x100],
+        0x40,
+        1 << 16,
+        [0x200040, 0x200000, 0, 0, 0x200000],
+    );
+    let header = Header::from_slice(&image).unwrap();
+    let plan = LoadPlan::with_quirks(&header, &image, quirks).unwrap();
+    assert_eq!(plan.entry(), 0x200000);
+}
+
+#[test]
+/// Place the modules right after the kernel.
+fn modules_after_kernel() {
+    let mut mem = machine();
+    let image = elf(0x100000, &[(0x100000, 0x100000, b"kernel", 0x2800)]);
+    let image = with_elf_header(image, 1);
+    let header = Header::from_slice(&image).unwrap();
+    let plan = LoadPlan::new(&header, &image).unwrap();
+    let memory_map = memory_map();
+    let modules = [
+        ModuleSource::new(b"first", Some("first")),
+        ModuleSource::new(b"second", Some("second")),
+    ];
+    let mut config = BootConfig {
+        header: &header,
+        load_plan: &plan,
+        image: &image,
+        modules: &modules,
+        command_line: Some("kernel"),
+        boot_loader_name: None,
+        memory_map: &memory_map,
+        quirks: Quirks::default(),
+    };
+    let registers = unsafe { config.boot(mem.mapper()).unwrap() };
+    let multiboot = unsafe { Multiboot::from_ptr(registers.ebx.into(), &mut mem).unwrap() };
+    assert!(multiboot.modules().unwrap().all(|m| m.end <= 0x100000));
+
+    config.quirks.modules_after_kernel = true;
+    let registers = unsafe { config.boot(mem.mapper()).unwrap() };
+    let multiboot = unsafe { Multiboot::from_ptr(registers.ebx.into(), &mut mem).unwrap() };
+    let starts: Vec<PAddr> = multiboot.modules().unwrap().map(|m| m.start).collect();
+    assert_eq!(starts[0], 0x103000);
+    assert!(starts[1] > 0x103000);
+    // the information itself stays where it was
+    assert!(PAddr::from(registers.ebx) < 0x100000);
+}
+
+#[test]
+/// Running out of memory for the information is an error.
+fn out_of_memory() {
+    let mem = machine();
+    let image = elf(0x100000, &[(0x100000, 0x100000, b"kernel", 0x1000)]);
+    let image = with_elf_header(image, 0);
+    let header = Header::from_slice(&image).unwrap();
+    let plan = LoadPlan::new(&header, &image).unwrap();
+    // there's only space for the information itself
+    let memory_map = [
+        MemoryEntry::new(0x1000, 0x80, MemoryType::Available),
+        MemoryEntry::new(0x100000, 0x1000, MemoryType::Available),
+    ];
+    let mut config = BootConfig {
+        header: &header,
+        load_plan: &plan,
+        image: &image,
+        modules: &[],
+        command_line: Some("/boot/kernel"),
+        boot_loader_name: None,
+        memory_map: &memory_map,
+        quirks: Quirks::default(),
+    };
+    assert_eq!(
+        unsafe { config.boot(mem.mapper()) },
+        Err(BootError::OutOfMemory)
+    );
+    config.command_line = None;
+    config.memory_map = &memory_map[1..];
+    assert_eq!(
+        unsafe { config.boot(mem.mapper()) },
+        Err(BootError::OutOfMemory)
+    );
+}
+
+#[test]
+/// Memory that is unavailable up to the end of the address space is skipped.
+fn allocate_below_hole() {
+    let mem = machine();
+    let memory_map = [
+        MemoryEntry::new(0x1000, 0x7000, MemoryType::Available),
+        MemoryEntry::new(0x3000, u64::MAX - 0x3000, MemoryType::Reserved),
+    ];
+    let mut allocator = PhysicalAllocator::new(&memory_map, &[], mem.mapper());
+    let request = AllocationRequest::new(0x1000, AllocationPurpose::Module).align(0x1000);
+    for &expected in [Some(0x1000), Some(0x2000), None].iter() {
+        let addr = unsafe { allocator.allocate_request(request) }.map(|(addr, _)| addr);
+        assert_eq!(addr, expected);
+    }
+}
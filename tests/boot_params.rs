@@ -0,0 +1,100 @@
+#![cfg(feature = "boot_params")]
+
+#[macro_use]
+extern crate multiboot;
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use multiboot::boot_params::{self, BootParam, ParamError};
+use multiboot::cmdline::{CommandLine, Word};
+
+static LOGLEVEL: AtomicU32 = AtomicU32::new(0);
+static EARLYCON: AtomicBool = AtomicBool::new(false);
+
+fn set_loglevel(value: Option<Word>) -> bool {
+    match value.and_then(|value| value.parse()) {
+        Some(level) => {
+            LOGLEVEL.store(level, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+fn set_earlycon(_value: Option<Word>) -> bool {
+    EARLYCON.store(true, Ordering::SeqCst);
+    true
+}
+
+boot_param!("loglevel", set_loglevel);
+boot_param!(early "earlycon", set_earlycon);
+
+#[test]
+/// Parameters declared with the macro end up in the registry.
+fn registry() {
+    let mut names: Vec<_> = boot_params::registered()
+        .map(|param| (param.name, param.early))
+        .collect();
+    names.sort();
+    assert_eq!(names, vec![("earlycon", true), ("loglevel", false)]);
+}
+
+#[test]
+/// Handlers are called in their stage, problems are reported.
+fn dispatch() {
+    static COUNT: AtomicU32 = AtomicU32::new(0);
+    fn count(_value: Option<Word>) -> bool {
+        COUNT.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+    fn reject(_value: Option<Word>) -> bool {
+        false
+    }
+    let params = [
+        BootParam {
+            name: "count",
+            handler: count,
+            early: false,
+        },
+        BootParam {
+            name: "early",
+            handler: count,
+            early: true,
+        },
+        BootParam {
+            name: "reject",
+            handler: reject,
+            early: false,
+        },
+    ];
+    let cmdline = CommandLine::new("/boot/kernel count early foo=1 count=2 reject=x -- bar");
+    let mut errors = Vec::new();
+    boot_params::dispatch_early(cmdline, &params, |error| errors.push(error));
+    assert_eq!(COUNT.load(Ordering::SeqCst), 1);
+    assert!(errors.is_empty());
+
+    boot_params::dispatch(cmdline, &params, |error| errors.push(error));
+    assert_eq!(COUNT.load(Ordering::SeqCst), 3);
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|error| match error {
+            ParamError::Unknown(param) => ("unknown", param.key.raw()),
+            ParamError::Invalid(param) => ("invalid", param.key.raw()),
+        })
+        .collect();
+    assert_eq!(errors, vec![("unknown", "foo"), ("invalid", "reject")]);
+}
+
+#[test]
+/// Dispatch the registered parameters.
+fn dispatch_registered() {
+    let cmdline = CommandLine::new("/boot/kernel earlycon loglevel=x loglevel=5");
+    let mut errors = 0;
+    boot_params::dispatch_early(cmdline, boot_params::registered(), |_| errors += 1);
+    assert!(EARLYCON.load(Ordering::SeqCst));
+    assert_eq!(LOGLEVEL.load(Ordering::SeqCst), 0);
+    boot_params::dispatch(cmdline, boot_params::registered(), |_| errors += 1);
+    assert_eq!(LOGLEVEL.load(Ordering::SeqCst), 5);
+    // the first value is invalid
+    assert_eq!(errors, 1);
+}
@@ -0,0 +1,16 @@
+#![cfg(feature = "boot_params")]
+
+extern crate multiboot;
+
+use multiboot::boot_params;
+use multiboot::cmdline::CommandLine;
+
+#[test]
+/// The registry links and is empty if no parameter is declared.
+fn empty_registry() {
+    assert_eq!(boot_params::registered().count(), 0);
+    let cmdline = CommandLine::new("/boot/kernel loglevel=7");
+    let mut unknown = 0;
+    boot_params::dispatch(cmdline, boot_params::registered(), |_| unknown += 1);
+    assert_eq!(unknown, 1);
+}
@@ -0,0 +1,124 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::Buffer;
+use multiboot::information::{
+    BootDevice, ColorDescriptor, ColorInfoRgb, ColorInfoType, FramebufferTable, MemoryEntry,
+    MemoryType, Module, Multiboot, MultibootInfoBuilder, PAddr, SymbolType,
+};
+
+const BASE: PAddr = 0x10000;
+
+#[test]
+/// An empty builder only writes the information struct.
+fn empty() {
+    let builder = MultibootInfoBuilder::new();
+    assert_eq!(builder.size(), 120);
+    let mut mem = Buffer::new(BASE, vec![0xff; 200]);
+    assert_eq!(builder.build(&mut mem.buffer, BASE), Some(120));
+    let multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+    assert!(multiboot.command_line().is_none());
+    assert!(multiboot.modules().is_none());
+    assert!(multiboot.memory_regions().is_none());
+    assert!(multiboot.framebuffer_table().is_none());
+    assert_eq!(multiboot.occupied_regions().count(), 1);
+}
+
+#[test]
+/// Write everything and read it back.
+fn everything() {
+    let memory_map = [
+        MemoryEntry::new(0, 0x9fc00, MemoryType::Available),
+        MemoryEntry::new(0x100000, 0x1f00000, MemoryType::Available),
+    ];
+    let modules = [
+        Module::new(0x200000, 0x201000, Some("/boot/initrd")),
+        Module::new(0x201000, 0x201800, None),
+    ];
+    let drives = [16, 0, 0, 0, 0x80, 1, 0, 1, 16, 63, 0, 0, 0, 0, 0, 0];
+    let sections = [0xab; 40 * 3];
+    let palette = [
+        ColorDescriptor {
+            red: 1,
+            green: 2,
+            blue: 3,
+        },
+        ColorDescriptor::default(),
+    ];
+    let rgb = ColorInfoRgb {
+        red_field_position: 0,
+        red_mask_size: 8,
+        green_field_position: 8,
+        green_mask_size: 8,
+        blue_field_position: 16,
+        blue_mask_size: 8,
+    };
+    let builder = MultibootInfoBuilder::new()
+        .memory_bounds((639, 31744))
+        .boot_device(BootDevice {
+            drive: 0x80,
+            partition1: 0,
+            partition2: 0xff,
+            partition3: 0xff,
+        })
+        .command_line("/boot/kernel quiet")
+        .boot_loader_name("builder")
+        .modules(&modules)
+        .elf_sections(&sections, 40, 2)
+        .memory_map(&memory_map)
+        .drives(&drives)
+        .framebuffer_table(FramebufferTable::new(
+            0xfd000000,
+            1024,
+            1024,
+            768,
+            8,
+            ColorInfoType::Rgb(rgb),
+        ))
+        .palette(&palette);
+    let size = builder.size();
+    let mut mem = Buffer::new(BASE, vec![0xff; size]);
+    assert!(builder.build(&mut mem.buffer[..size - 1], BASE).is_none());
+    assert!(builder.build(&mut mem.buffer, 0xffff_ff00).is_none());
+    assert_eq!(builder.build(&mut mem.buffer, BASE), Some(size));
+
+    let multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+    assert_eq!(multiboot.lower_memory_bound(), Some(639));
+    assert_eq!(multiboot.upper_memory_bound(), Some(31744));
+    assert_eq!(multiboot.boot_device().unwrap().drive, 0x80);
+    assert_eq!(multiboot.command_line(), Some("/boot/kernel quiet"));
+    assert_eq!(multiboot.boot_loader_name(), Some("builder"));
+    let read: Vec<Module> = multiboot.modules().unwrap().collect();
+    assert_eq!(read, modules);
+    match multiboot.symbols() {
+        Some(SymbolType::Elf(_)) => {}
+        symbols => panic!("unexpected symbols: {:?}", symbols),
+    }
+    let regions: Vec<(PAddr, PAddr)> = multiboot
+        .memory_regions()
+        .unwrap()
+        .map(|r| (r.base_address(), r.length()))
+        .collect();
+    assert_eq!(regions, [(0, 0x9fc00), (0x100000, 0x1f00000)]);
+    let table = multiboot.framebuffer_table().unwrap();
+    assert_eq!(table.width, 1024);
+    assert!(matches!(
+        table.color_info(),
+        Some(ColorInfoType::Palette(_))
+    ));
+
+    // everything is within the buffer, except for the modules
+    let mut kinds = 0;
+    for region in multiboot.occupied_regions() {
+        if region.range.start >= 0x200000 {
+            continue;
+        }
+        assert!(region.range.start >= BASE, "{:?}", region);
+        assert!(region.range.end <= BASE + size as PAddr, "{:?}", region);
+        kinds += 1;
+    }
+    // info, strings, module table and string, ELF section headers, memory map, drives, palette
+    assert_eq!(kinds, 9);
+}
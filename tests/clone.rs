@@ -0,0 +1,136 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::Buffer;
+use multiboot::information::{
+    ColorDescriptor, ColorInfoType, FramebufferTable, MemoryEntry, MemoryType, Module, Multiboot,
+    MultibootInfoBuilder, OccupiedRegion, OccupiedRegionKind, PAddr, SymbolType,
+};
+
+const BASE: PAddr = 0x10000;
+
+/// Build an information with most fields set at `BASE`.
+fn original() -> Buffer {
+    let memory_map = [
+        MemoryEntry::new(0, 0x9fc00, MemoryType::Available),
+        MemoryEntry::new(0x100000, 0x1f00000, MemoryType::Available),
+    ];
+    let modules = [
+        Module::new(0x200000, 0x201000, Some("/boot/initrd")),
+        Module::new(0x201000, 0x201800, None),
+    ];
+    let tables = [0x5a; 4 + 12 + 4 + 7];
+    let apm = [0xa5; 20];
+    let control_info = [1; 512];
+    let mode_info = [2; 256];
+    let palette = [ColorDescriptor {
+        red: 1,
+        green: 2,
+        blue: 3,
+    }; 4];
+    let builder = MultibootInfoBuilder::new()
+        .memory_bounds((639, 31744))
+        .command_line("/boot/kernel quiet")
+        .boot_loader_name("original")
+        .modules(&modules)
+        .aout_symbols(&tables, 12, 7)
+        .memory_map(&memory_map)
+        .drives(&[4, 0, 0, 0])
+        .config_table(0xf0000)
+        .apm_table(&apm)
+        .vbe(&control_info, &mode_info, 0x118, (0xc000, 0x10, 0x100))
+        .framebuffer_table(FramebufferTable::new(
+            0xfd000000,
+            1024,
+            1024,
+            768,
+            8,
+            ColorInfoType::Text,
+        ))
+        .palette(&palette);
+    Buffer::build(&builder, BASE)
+}
+
+#[test]
+/// Clone to a physical and a higher half address and read the copy.
+fn clone() {
+    for &new_base in &[0x300000, 0xc010_0000] {
+        let mut mem = original();
+        let (size, buffer) = {
+            let multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+            let size = multiboot.clone_size().unwrap();
+            let mut buffer = vec![0xff; size];
+            assert!(multiboot
+                .clone_into(&mut buffer[..size - 1], new_base)
+                .is_none());
+            assert_eq!(multiboot.clone_into(&mut buffer, new_base), Some(size));
+            (size, buffer)
+        };
+        // the original is gone
+        let mut mem = Buffer::new(new_base, buffer);
+        let multiboot = unsafe { Multiboot::from_ptr(new_base, &mut mem).unwrap() };
+        assert_eq!(multiboot.lower_memory_bound(), Some(639));
+        assert_eq!(multiboot.command_line(), Some("/boot/kernel quiet"));
+        assert_eq!(multiboot.boot_loader_name(), Some("original"));
+        let modules: Vec<Module> = multiboot.modules().unwrap().collect();
+        assert_eq!(
+            modules,
+            [
+                Module::new(0x200000, 0x201000, Some("/boot/initrd")),
+                Module::new(0x201000, 0x201800, None),
+            ]
+        );
+        match multiboot.symbols() {
+            Some(SymbolType::AOut(_)) => {}
+            symbols => panic!("unexpected symbols: {:?}", symbols),
+        }
+        assert_eq!(multiboot.memory_regions().unwrap().count(), 2);
+        let table = multiboot.framebuffer_table().unwrap();
+        match table.color_info() {
+            Some(ColorInfoType::Palette(_)) => {}
+            info => panic!("unexpected color info: {:?}", info),
+        }
+
+        // everything that was copied is within the buffer
+        let mut kinds = 0;
+        for region in multiboot.occupied_regions() {
+            if let OccupiedRegionKind::Module(_) = region.kind {
+                continue;
+            }
+            assert!(region.range.start >= new_base, "{:?}", region);
+            assert!(region.range.end <= new_base + size as PAddr, "{:?}", region);
+            kinds += 1;
+        }
+        // info, strings, module table and string, symbols, memory map, drives, APM, VBE, palette
+        assert_eq!(kinds, 12);
+
+        // only the modules still need to be preserved
+        let references: Vec<OccupiedRegion> = multiboot.clone_references().collect();
+        assert_eq!(
+            references,
+            [
+                OccupiedRegion {
+                    kind: OccupiedRegionKind::Module(0),
+                    range: 0x200000..0x201000,
+                },
+                OccupiedRegion {
+                    kind: OccupiedRegionKind::Module(1),
+                    range: 0x201000..0x201800,
+                },
+            ]
+        );
+    }
+}
+
+#[test]
+/// Cloning fails if something isn't mapped.
+fn unmapped() {
+    let mut mem = original();
+    let mut multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+    multiboot.set_memory_regions(Some((0x800000, 2)));
+    assert!(multiboot.clone_size().is_none());
+    let mut buffer = [0; 4096];
+    assert!(multiboot.clone_into(&mut buffer, 0x300000).is_none());
+}
@@ -0,0 +1,104 @@
+extern crate multiboot;
+
+use multiboot::cmdline::{CommandLine, Param};
+use multiboot::information::Module;
+
+#[test]
+/// Split a GRUB command line into path, parameters and init arguments.
+fn parts() {
+    let cmdline =
+        CommandLine::new("  /boot/kernel console=ttyS0,115200 quiet\tnosmp -- single  x=1 ");
+    assert_eq!(cmdline.path().unwrap(), "/boot/kernel");
+    assert_eq!(
+        cmdline.arguments(),
+        "console=ttyS0,115200 quiet\tnosmp -- single  x=1 "
+    );
+    let params: Vec<_> = cmdline
+        .params()
+        .map(|param| (param.key.raw(), param.value.map(|v| v.raw())))
+        .collect();
+    assert_eq!(
+        params,
+        vec![
+            ("console", Some("ttyS0,115200")),
+            ("quiet", None),
+            ("nosmp", None),
+        ]
+    );
+    let init: Vec<_> = cmdline.init_args().map(|arg| arg.raw()).collect();
+    assert_eq!(init, vec!["single", "x=1"]);
+    // things after -- aren't parameters
+    assert!(!cmdline.contains("x"));
+
+    let empty = CommandLine::new("");
+    assert!(empty.path().is_none());
+    assert_eq!(empty.params().count(), 0);
+    assert_eq!(empty.init_args().count(), 0);
+    assert_eq!(CommandLine::new("/boot/kernel").arguments(), "");
+}
+
+#[test]
+/// Quotes group words and backslashes escape characters.
+fn quoting() {
+    let cmdline = CommandLine::new(
+        r#""/boot/my kernel" msg="hello world" 'a b'=c path=C:\\dir esc=\"x\ y\" single='\n'"#,
+    );
+    assert_eq!(cmdline.path().unwrap(), "/boot/my kernel");
+    assert_eq!(cmdline.path().unwrap().raw(), r#""/boot/my kernel""#);
+    let msg = cmdline.value("msg").unwrap();
+    assert_eq!(msg, "hello world");
+    assert_eq!(msg.as_str(), None);
+    assert_eq!(msg.to_string(), "hello world");
+    assert_eq!(cmdline.value("a b").unwrap(), "c");
+    assert_eq!(cmdline.value("path").unwrap(), r"C:\dir");
+    assert_eq!(cmdline.value("esc").unwrap(), r#""x y""#);
+    assert_eq!(cmdline.value("single").unwrap(), r"\n");
+    assert_eq!(cmdline.params().count(), 5);
+
+    let mut buffer = [0; 11];
+    assert_eq!(msg.unescape_into(&mut buffer), Some("hello world"));
+    assert_eq!(msg.unescape_into(&mut buffer[..10]), None);
+
+    let param = Param::from_word(cmdline.words().nth(2).unwrap());
+    assert_eq!(param.key.raw(), "'a b'");
+}
+
+#[test]
+/// Typed lookups, flags and repeated keys.
+fn lookups() {
+    let cmdline = CommandLine::new(
+        "kernel loglevel=4 console=tty0 console=ttyS0 debug=off debug nosmp=0 acpi=on \
+         mem=\"512\" loglevel=7 bad=x1 noval=",
+    );
+    assert_eq!(cmdline.get::<u32>("loglevel"), Some(7));
+    assert_eq!(cmdline.get::<u64>("mem"), Some(512));
+    assert_eq!(cmdline.get::<u32>("bad"), None);
+    assert_eq!(cmdline.get::<u32>("missing"), None);
+    assert_eq!(cmdline.value("noval").unwrap(), "");
+    let consoles: Vec<_> = cmdline.values("console").map(|v| v.raw()).collect();
+    assert_eq!(consoles, vec!["tty0", "ttyS0"]);
+    assert_eq!(cmdline.value("console").unwrap(), "ttyS0");
+
+    assert!(cmdline.flag("debug"));
+    assert!(!cmdline.flag("nosmp"));
+    assert!(cmdline.flag("acpi"));
+    assert!(!cmdline.flag("quiet"));
+    assert!(cmdline.contains("nosmp"));
+    // a bare flag has no value
+    assert!(cmdline.value("debug").is_none());
+}
+
+#[test]
+/// Module strings are parsed the same way.
+fn module() {
+    let module = Module::new(
+        0x100000,
+        0x200000,
+        Some("/boot/initrd.img root=/dev/sda1 ro"),
+    );
+    let cmdline = module.command_line().unwrap();
+    assert_eq!(cmdline.path().unwrap(), "/boot/initrd.img");
+    assert_eq!(cmdline.value("root").unwrap(), "/dev/sda1");
+    assert!(cmdline.flag("ro"));
+    assert!(Module::new(0, 0, None).command_line().is_none());
+}
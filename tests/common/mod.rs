@@ -0,0 +1,136 @@
+//! Simulated physical memory shared by the integration tests.
+
+#![allow(dead_code)]
+
+use core::mem;
+use core::slice;
+use multiboot::information::{
+    MemoryAllocator, MemoryEntry, Multiboot, MultibootInfo, MultibootInfoBuilder, PAddr,
+    PhysicalMemoryMapper,
+};
+
+/// Simulated physical memory, starting at address 0.
+pub struct Mem {
+    pub memory: &'static mut [u8],
+    /// Allocations are placed at or after this address.
+    pub next_free: usize,
+    /// Every address that was deallocated.
+    pub freed: Vec<PAddr>,
+}
+
+impl Mem {
+    pub fn new(size: usize, next_free: usize) -> Self {
+        Self {
+            memory: Box::leak(vec![0; size].into_boxed_slice()),
+            next_free,
+            freed: Vec::new(),
+        }
+    }
+
+    /// Write some bytes to physical memory.
+    pub fn write(&mut self, addr: PAddr, bytes: &[u8]) {
+        let addr = addr as usize;
+        self.memory[addr..addr + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Get a mutable slice of physical memory.
+    pub fn map(&self, addr: PAddr, size: usize) -> &'static mut [u8] {
+        (self.mapper())(addr, size).unwrap()
+    }
+
+    /// Get a function that maps physical memory, independent of `self`.
+    ///
+    /// Address 0 and anything past the end of memory can't be mapped.
+    pub fn mapper(&self) -> impl Fn(PAddr, usize) -> Option<&'static mut [u8]> {
+        let base = self.memory.as_ptr() as usize;
+        let length = self.memory.len();
+        move |addr, size| {
+            let addr = addr as usize;
+            if addr == 0 || addr + size > length {
+                return None;
+            }
+            Some(unsafe { slice::from_raw_parts_mut((base + addr) as *mut u8, size) })
+        }
+    }
+}
+
+impl PhysicalMemoryMapper for Mem {
+    unsafe fn paddr_to_slice(&self, addr: PAddr, size: usize) -> Option<&'static [u8]> {
+        (self.mapper())(addr, size).map(|slice| &*slice)
+    }
+}
+
+impl MemoryAllocator for Mem {
+    unsafe fn allocate(&mut self, length: usize) -> Option<(PAddr, &mut [u8])> {
+        self.allocate_aligned(length, 8)
+    }
+
+    unsafe fn allocate_aligned(
+        &mut self,
+        length: usize,
+        align: usize,
+    ) -> Option<(PAddr, &mut [u8])> {
+        let addr = self.next_free.next_multiple_of(align);
+        if addr + length > self.memory.len() {
+            return None;
+        }
+        self.next_free = addr + length;
+        let ptr = self.memory.as_mut_ptr().add(addr);
+        Some((addr as PAddr, slice::from_raw_parts_mut(ptr, length)))
+    }
+
+    unsafe fn deallocate(&mut self, addr: PAddr) {
+        self.freed.push(addr);
+    }
+}
+
+/// Physical memory that consists of one buffer at `base` and can't allocate.
+pub struct Buffer {
+    pub base: PAddr,
+    pub buffer: Vec<u8>,
+}
+
+impl Buffer {
+    pub fn new(base: PAddr, buffer: Vec<u8>) -> Self {
+        Self { base, buffer }
+    }
+
+    /// Build the information into a new buffer at `base`.
+    pub fn build(builder: &MultibootInfoBuilder, base: PAddr) -> Self {
+        let mut buffer = vec![0; builder.size()];
+        builder.build(&mut buffer, base).unwrap();
+        Self::new(base, buffer)
+    }
+}
+
+impl PhysicalMemoryMapper for Buffer {
+    unsafe fn paddr_to_slice(&self, addr: PAddr, size: usize) -> Option<&'static [u8]> {
+        let offset = addr.checked_sub(self.base)? as usize;
+        if offset + size > self.buffer.len() {
+            return None;
+        }
+        Some(slice::from_raw_parts(
+            self.buffer.as_ptr().add(offset),
+            size,
+        ))
+    }
+}
+
+/// Write an empty information struct to `addr`.
+pub fn write_info(mem: &mut Mem, addr: PAddr) {
+    let info = MultibootInfo::default();
+    let info: [u8; 120] = unsafe { mem::transmute::<MultibootInfo, [u8; 120]>(info) };
+    mem.write(addr, &info);
+}
+
+/// Write an information struct to `addr` and the memory map right behind it.
+pub fn write_info_with_map(mem: &mut Mem, addr: PAddr, regions: &[MemoryEntry]) {
+    write_info(mem, addr);
+    let mmap_addr = addr + mem::size_of::<MultibootInfo>() as PAddr;
+    for (index, region) in regions.iter().enumerate() {
+        let region: [u8; 24] = unsafe { mem::transmute::<MemoryEntry, [u8; 24]>(*region) };
+        mem.write(mmap_addr + index as PAddr * 24, &region);
+    }
+    let mut multiboot = unsafe { Multiboot::from_ptr(addr, mem).unwrap() };
+    multiboot.set_memory_regions(Some((mmap_addr, regions.len())));
+}
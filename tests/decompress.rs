@@ -0,0 +1,259 @@
+#![cfg(feature = "decompress")]
+
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::{write_info, Mem};
+use multiboot::decompress::{self, Compression, DecompressError};
+use multiboot::information::{
+    AllocationPurpose, AllocationRequest, MemoryAllocator, Module, Multiboot, PAddr,
+    PhysicalMemoryMapper,
+};
+
+/// The uncompressed contents of the fixtures.
+fn contents() -> Vec<u8> {
+    let mut contents = Vec::new();
+    for line in 0..100 {
+        contents.extend_from_slice(format!("line {}\n", line).as_bytes());
+    }
+    for _ in 0..8000 {
+        contents.extend_from_slice(b"multiboot ");
+    }
+    contents
+}
+
+/// gzip of `contents()`, with a file name.
+const GZIP: [u8; 432] = [
+    0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x69, 0x6e, 0x69, 0x74, 0x72, 0x64,
+    0x2e, 0x63, 0x70, 0x69, 0x6f, 0x00, 0xed, 0xd2, 0xbb, 0x6d, 0xdd, 0x40, 0x00, 0x45, 0xc1, 0x5c,
+    0x55, 0xa8, 0x04, 0xf3, 0xb7, 0xe4, 0xb6, 0x63, 0xc0, 0x81, 0x80, 0x27, 0x2b, 0x91, 0xfb, 0x37,
+    0x0c, 0x1e, 0xc2, 0x4d, 0x4c, 0x74, 0x27, 0xe1, 0x01, 0xc9, 0xdd, 0xd7, 0xc7, 0xef, 0x5f, 0xef,
+    0x3f, 0xde, 0x5e, 0xff, 0x66, 0xb9, 0x67, 0xbd, 0x67, 0xbb, 0x67, 0xbf, 0xe7, 0xb8, 0x67, 0xdc,
+    0x73, 0xde, 0x73, 0xdd, 0x33, 0x7b, 0xfc, 0xc9, 0xd4, 0x59, 0x0a, 0x2d, 0x95, 0x96, 0x52, 0x4b,
+    0xad, 0xa5, 0xd8, 0x52, 0x6d, 0x29, 0xb7, 0xd4, 0x5b, 0xeb, 0xad, 0xcf, 0x7b, 0xd5, 0x5b, 0xeb,
+    0xad, 0xf5, 0xd6, 0x7a, 0x6b, 0xbd, 0xb5, 0xde, 0x5a, 0x6f, 0xad, 0xb7, 0xd5, 0xdb, 0xea, 0x6d,
+    0xcf, 0x87, 0xd6, 0xdb, 0xea, 0x6d, 0xf5, 0xb6, 0x7a, 0x5b, 0xbd, 0xad, 0xde, 0x56, 0x6f, 0xaf,
+    0xb7, 0xd7, 0xdb, 0xeb, 0xed, 0xcf, 0x9f, 0xab, 0xb7, 0xd7, 0xdb, 0xeb, 0xed, 0xf5, 0xf6, 0x7a,
+    0x7b, 0xbd, 0xa3, 0xde, 0x51, 0xef, 0xa8, 0x77, 0xd4, 0x3b, 0x9e, 0xa3, 0xa8, 0x77, 0xd4, 0x3b,
+    0xea, 0x1d, 0xf5, 0x8e, 0x7a, 0xa3, 0xde, 0xa8, 0x37, 0xea, 0x8d, 0x7a, 0xa3, 0xde, 0x78, 0xce,
+    0xb6, 0xde, 0xa8, 0x37, 0xea, 0x8d, 0x7a, 0x67, 0xbd, 0xb3, 0xde, 0x59, 0xef, 0xac, 0x77, 0xd6,
+    0x3b, 0xeb, 0x9d, 0xcf, 0x65, 0xa9, 0x77, 0xd6, 0x3b, 0xeb, 0x5d, 0xf5, 0xae, 0x7a, 0x57, 0xbd,
+    0xab, 0xde, 0x55, 0xef, 0xaa, 0x77, 0xd5, 0xbb, 0x9e, 0xdb, 0x57, 0xef, 0xaa, 0x37, 0xeb, 0xcd,
+    0x7a, 0xb3, 0xde, 0xac, 0x37, 0xeb, 0xcd, 0x7a, 0xb3, 0xde, 0xac, 0x37, 0x9f, 0xeb, 0x3c, 0xdf,
+    0x3e, 0xff, 0xbc, 0xbe, 0x3f, 0x7e, 0x7e, 0x7d, 0x7d, 0xbf, 0x13, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0xfd, 0xd7, 0x5f, 0xe5, 0x21, 0x10, 0xae, 0x96, 0x3b, 0x01, 0x00,
+];
+
+/// zlib of `contents()`.
+const ZLIB: [u8; 408] = [
+    0x78, 0xda, 0xed, 0xd2, 0xbb, 0x6d, 0xdd, 0x40, 0x00, 0x45, 0xc1, 0x5c, 0x55, 0xa8, 0x04, 0xf3,
+    0xb7, 0xe4, 0xb6, 0x63, 0xc0, 0x81, 0x80, 0x27, 0x2b, 0x91, 0xfb, 0x37, 0x0c, 0x1e, 0xc2, 0x4d,
+    0x4c, 0x74, 0x27, 0xe1, 0x01, 0xc9, 0xdd, 0xd7, 0xc7, 0xef, 0x5f, 0xef, 0x3f, 0xde, 0x5e, 0xff,
+    0x66, 0xb9, 0x67, 0xbd, 0x67, 0xbb, 0x67, 0xbf, 0xe7, 0xb8, 0x67, 0xdc, 0x73, 0xde, 0x73, 0xdd,
+    0x33, 0x7b, 0xfc, 0xc9, 0xd4, 0x59, 0x0a, 0x2d, 0x95, 0x96, 0x52, 0x4b, 0xad, 0xa5, 0xd8, 0x52,
+    0x6d, 0x29, 0xb7, 0xd4, 0x5b, 0xeb, 0xad, 0xcf, 0x7b, 0xd5, 0x5b, 0xeb, 0xad, 0xf5, 0xd6, 0x7a,
+    0x6b, 0xbd, 0xb5, 0xde, 0x5a, 0x6f, 0xad, 0xb7, 0xd5, 0xdb, 0xea, 0x6d, 0xcf, 0x87, 0xd6, 0xdb,
+    0xea, 0x6d, 0xf5, 0xb6, 0x7a, 0x5b, 0xbd, 0xad, 0xde, 0x56, 0x6f, 0xaf, 0xb7, 0xd7, 0xdb, 0xeb,
+    0xed, 0xcf, 0x9f, 0xab, 0xb7, 0xd7, 0xdb, 0xeb, 0xed, 0xf5, 0xf6, 0x7a, 0x7b, 0xbd, 0xa3, 0xde,
+    0x51, 0xef, 0xa8, 0x77, 0xd4, 0x3b, 0x9e, 0xa3, 0xa8, 0x77, 0xd4, 0x3b, 0xea, 0x1d, 0xf5, 0x8e,
+    0x7a, 0xa3, 0xde, 0xa8, 0x37, 0xea, 0x8d, 0x7a, 0xa3, 0xde, 0x78, 0xce, 0xb6, 0xde, 0xa8, 0x37,
+    0xea, 0x8d, 0x7a, 0x67, 0xbd, 0xb3, 0xde, 0x59, 0xef, 0xac, 0x77, 0xd6, 0x3b, 0xeb, 0x9d, 0xcf,
+    0x65, 0xa9, 0x77, 0xd6, 0x3b, 0xeb, 0x5d, 0xf5, 0xae, 0x7a, 0x57, 0xbd, 0xab, 0xde, 0x55, 0xef,
+    0xaa, 0x77, 0xd5, 0xbb, 0x9e, 0xdb, 0x57, 0xef, 0xaa, 0x37, 0xeb, 0xcd, 0x7a, 0xb3, 0xde, 0xac,
+    0x37, 0xeb, 0xcd, 0x7a, 0xb3, 0xde, 0xac, 0x37, 0x9f, 0xeb, 0x3c, 0xdf, 0x3e, 0xff, 0xbc, 0xbe,
+    0x3f, 0x7e, 0x7e, 0x7d, 0x7d, 0xbf, 0x13, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+    0x11, 0xfd, 0xd7, 0x5f, 0x76, 0xdc, 0xc5, 0x40,
+];
+
+#[test]
+/// Decompress into a buffer.
+fn buffer() {
+    assert_eq!(Compression::detect(&GZIP), Some(Compression::Gzip));
+    assert_eq!(Compression::detect(&ZLIB), Some(Compression::Zlib));
+    assert_eq!(Compression::detect(b"070701"), None);
+    let contents = contents();
+    let mut out = vec![0; contents.len()];
+    for data in [&GZIP[..], &ZLIB[..]].iter() {
+        assert_eq!(decompress::decompressed_size(data), Ok(contents.len()));
+        assert_eq!(
+            decompress::decompress_into(data, &mut out),
+            Ok(contents.len())
+        );
+        assert_eq!(out, contents);
+        assert_eq!(
+            decompress::decompress_into(data, &mut out[..1000]),
+            Err(DecompressError::BufferTooSmall)
+        );
+    }
+    assert_eq!(
+        decompress::decompressed_size(b"plain"),
+        Err(DecompressError::UnknownFormat)
+    );
+}
+
+#[test]
+/// Broken data is detected.
+fn corrupt() {
+    let mut out = vec![0; contents().len()];
+    // the CRC is wrong
+    let mut gzip = GZIP;
+    gzip[GZIP.len() - 8] ^= 1;
+    assert_eq!(
+        decompress::decompress_into(&gzip, &mut out),
+        Err(DecompressError::ChecksumMismatch)
+    );
+    // the Adler-32 is wrong
+    let mut zlib = ZLIB;
+    zlib[ZLIB.len() - 1] ^= 1;
+    assert_eq!(
+        decompress::decompress_into(&zlib, &mut out),
+        Err(DecompressError::ChecksumMismatch)
+    );
+    assert_eq!(
+        decompress::decompress_into(&ZLIB[..100], &mut out),
+        Err(DecompressError::Corrupt)
+    );
+    assert_eq!(
+        decompress::decompressed_size(&ZLIB[..100]),
+        Err(DecompressError::Corrupt)
+    );
+}
+
+#[test]
+/// Decompress a module and update the module table.
+fn module() {
+    let mut mem = Mem::new(0x21000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    mem.write(0x20000, &GZIP);
+    mem.write(0x20800, &ZLIB);
+    mem.write(0x20f00, b"plain");
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_modules(Some(&[
+        Module::new(
+            0x20000,
+            0x20000 + GZIP.len() as PAddr,
+            Some("/boot/initrd.gz"),
+        ),
+        Module::new(0x20800, 0x20800 + ZLIB.len() as PAddr, Some("/boot/data.z")),
+        Module::new(0x20f00, 0x20f05, Some("plain")),
+    ]));
+    // the module strings and the table are allocated at 0x2000
+    let module = multiboot.decompress_module(0).unwrap();
+    let length = contents().len() as PAddr;
+    assert_eq!(module.start, 0x2058);
+    assert_eq!(module.end, 0x2058 + length);
+    assert_eq!(module.string, Some("/boot/initrd.gz"));
+    let modules: Vec<_> = multiboot.modules().unwrap().collect();
+    assert_eq!(modules[0], module);
+    assert_eq!(multiboot.module_data(&modules[0]).unwrap(), &contents()[..]);
+    assert_eq!(modules[1].start, 0x20800);
+
+    assert_eq!(
+        multiboot.decompress_module(2),
+        Err(DecompressError::UnknownFormat)
+    );
+    assert_eq!(
+        multiboot.decompress_module(3),
+        Err(DecompressError::Inaccessible)
+    );
+    // there's not enough memory left for a second copy
+    assert_eq!(
+        multiboot.decompress_module(1),
+        Err(DecompressError::AllocationFailed)
+    );
+}
+
+#[test]
+/// Decompress a module without touching the table.
+fn module_copy() {
+    let mut mem = Mem::new(0x40000, 0x10000);
+    mem.write(0x8000, &ZLIB);
+    let module = Module::new(0x8000, 0x8000 + ZLIB.len() as PAddr, None);
+    let copy = unsafe { decompress::decompress_module(&module, &mut mem).unwrap() };
+    assert_eq!(unsafe { copy.data(&mem) }.unwrap(), &contents()[..]);
+    // failed decompression frees the buffer again
+    let mut gzip = GZIP;
+    gzip[GZIP.len() - 8] ^= 1;
+    mem.write(0x8000, &gzip);
+    let module = Module::new(0x8000, 0x8000 + GZIP.len() as PAddr, None);
+    assert_eq!(
+        unsafe { decompress::decompress_module(&module, &mut mem) },
+        Err(DecompressError::ChecksumMismatch)
+    );
+    assert_eq!(mem.freed, vec![0x10000 + round_up(copy.end - copy.start)]);
+}
+
+fn round_up(addr: PAddr) -> PAddr {
+    (addr + 7) & !7
+}
+
+/// Places modules above 4 GiB, the rest of memory is mirrored there.
+struct HighModules(Mem);
+
+const HIGH: PAddr = 1 << 32;
+
+impl PhysicalMemoryMapper for HighModules {
+    unsafe fn paddr_to_slice(&self, addr: PAddr, size: usize) -> Option<&'static [u8]> {
+        self.0.paddr_to_slice(addr % HIGH, size)
+    }
+}
+
+impl MemoryAllocator for HighModules {
+    unsafe fn allocate(&mut self, length: usize) -> Option<(PAddr, &mut [u8])> {
+        self.0.allocate(length)
+    }
+
+    unsafe fn allocate_request(
+        &mut self,
+        request: AllocationRequest,
+    ) -> Option<(PAddr, &mut [u8])> {
+        let high = request.purpose == AllocationPurpose::Module;
+        let (addr, buffer) = self.0.allocate_request(request)?;
+        Some((if high { addr + HIGH } else { addr }, buffer))
+    }
+
+    unsafe fn deallocate(&mut self, addr: PAddr) {
+        self.0.deallocate(addr)
+    }
+}
+
+#[test]
+/// Decompressed modules that end up above 4 GiB are rejected.
+fn module_above_4gib() {
+    let mut mem = HighModules(Mem::new(0x21000, 0x2000));
+    write_info(&mut mem.0, 0x1000);
+    mem.0.write(0x20000, &GZIP);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_modules(Some(&[Module::new(
+        0x20000,
+        0x20000 + GZIP.len() as PAddr,
+        None,
+    )]));
+    assert_eq!(
+        multiboot.decompress_module(0),
+        Err(DecompressError::AllocationFailed)
+    );
+    assert_eq!(multiboot.modules().unwrap().next().unwrap().start, 0x20000);
+    // the copy was placed right after the table
+    assert_eq!(mem.0.freed, vec![HIGH + 0x2010]);
+}
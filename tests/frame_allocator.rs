@@ -0,0 +1,159 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::{write_info_with_map, Mem};
+use multiboot::frame_allocator::{
+    BitmapFrameAllocator, BumpFrameAllocator, FrameAllocator, FrameSize, MAX_REGIONS,
+};
+use multiboot::information::{MemoryEntry, MemoryType, Multiboot, PAddr};
+
+/// Write an information struct and the memory map to 0x500.
+///
+/// The map is 640 KiB of lower memory and 128 MiB of upper memory.
+fn simulated_machine() -> Mem {
+    let mut mem = Mem::new(0x10000, 0);
+    let regions = [
+        MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available),
+        MemoryEntry::new(0x9fc00, 0x400, MemoryType::Reserved),
+        MemoryEntry::new(0xf0000, 0x10000, MemoryType::Reserved),
+        MemoryEntry::new(0x100000, 0x8000000, MemoryType::Available),
+    ];
+    write_info_with_map(&mut mem, 0x500, &regions);
+    mem
+}
+
+#[test]
+/// Allocate from the bottom up and free the last frame.
+fn bump() {
+    let mut mem = simulated_machine();
+    let multiboot = unsafe { Multiboot::from_ptr(0x500, &mut mem).unwrap() };
+    let kernel = [0x100000..0x180000, 0x180000..0x180000];
+    let mut allocator = BumpFrameAllocator::new(&multiboot, &kernel).unwrap();
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x1000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x2000));
+    // this doesn't fit into lower memory
+    assert_eq!(allocator.allocate_frame(FrameSize::Large), Some(0x200000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x400000));
+    allocator.deallocate_frame(0x400000, FrameSize::Small);
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x400000));
+    // this one is leaked
+    allocator.deallocate_frame(0x1000, FrameSize::Small);
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x401000));
+}
+
+#[test]
+/// Allocate until no memory is left.
+fn bump_exhausted() {
+    let mut allocator = BumpFrameAllocator::from_regions(vec![0x1000..0x3000, 0x5000..0x6000]);
+    assert_eq!(allocator.allocate_frame(FrameSize::Large), None);
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x1000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x2000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x5000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), None);
+}
+
+#[test]
+/// Regions are used from the lowest address upwards, whatever their order.
+fn bump_unsorted() {
+    let mut allocator = BumpFrameAllocator::from_regions(vec![0x5000..0x6000, 0x1000..0x2000]);
+    assert_eq!(allocator.dropped_regions(), 0);
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x1000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x5000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), None);
+}
+
+#[test]
+/// Only the lowest regions are kept if there are too many.
+fn bump_too_many_regions() {
+    let regions = (0..MAX_REGIONS as PAddr + 2)
+        .rev()
+        .map(|index| (index + 1) * 0x2000..(index + 1) * 0x2000 + 0x1000);
+    let mut allocator = BumpFrameAllocator::from_regions(regions);
+    assert_eq!(allocator.dropped_regions(), 2);
+    for index in 0..MAX_REGIONS as PAddr {
+        let frame = (index + 1) * 0x2000;
+        assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(frame));
+    }
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), None);
+}
+
+#[test]
+/// Allocate and free small and large frames.
+fn bitmap() {
+    let mut mem = simulated_machine();
+    let phys = mem.mapper();
+    let multiboot = unsafe { Multiboot::from_ptr(0x500, &mut mem).unwrap() };
+    let kernel = 0x100000..0x180000;
+    let mut allocator = unsafe {
+        BitmapFrameAllocator::new(&multiboot, &[kernel], |addr, size| {
+            phys(addr, size).unwrap()
+        })
+        .unwrap()
+    };
+    // 130 MiB need a bitmap of two pages, this is placed in the first free pages
+    assert_eq!(allocator.bitmap_region(), 0x1000..0x3000);
+    let lower = (0x9f000 - 0x3000) / 4096;
+    let upper = (0x8100000 - 0x180000) / 4096;
+    assert_eq!(allocator.free_frames(), lower + upper);
+
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x3000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x4000));
+    // the first 2 MiB contain the kernel and the bitmap
+    assert_eq!(allocator.allocate_frame(FrameSize::Large), Some(0x200000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Large), Some(0x400000));
+    assert_eq!(allocator.free_frames(), lower + upper - 2 - 2 * 512);
+
+    allocator.deallocate_frame(0x3000, FrameSize::Small);
+    allocator.deallocate_frame(0x200000, FrameSize::Large);
+    assert_eq!(allocator.free_frames(), lower + upper - 1 - 512);
+    assert_eq!(allocator.allocate_frame(FrameSize::Large), Some(0x600000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x800000));
+    // wrap around to the freed frames
+    for _ in 0..(0x8100000 - 0x801000) / 4096 {
+        allocator.allocate_frame(FrameSize::Small).unwrap();
+    }
+    assert_eq!(allocator.allocate_frame(FrameSize::Large), Some(0x200000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x3000));
+}
+
+#[test]
+/// Allocate until no memory is left.
+fn bitmap_exhausted() {
+    let phys = Mem::new(0x10000, 0);
+    let regions = [0x1800..0x4000, 0x8000..0x9000];
+    let mut allocator = unsafe {
+        BitmapFrameAllocator::from_regions(&regions, |addr, size| phys.map(addr, size)).unwrap()
+    };
+    assert_eq!(allocator.bitmap_region(), 0x2000..0x3000);
+    assert_eq!(allocator.free_frames(), 2);
+    assert_eq!(allocator.allocate_frame(FrameSize::Large), None);
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x3000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), Some(0x8000));
+    assert_eq!(allocator.allocate_frame(FrameSize::Small), None);
+}
+
+#[test]
+/// Frames outside of the bitmap and the bitmap itself are never freed.
+fn bitmap_invalid_free() {
+    let phys = Mem::new(0x10000, 0);
+    let regions = [0x1000..0x3000, 0x3000..0x4000];
+    let mut allocator = unsafe {
+        BitmapFrameAllocator::from_regions(&regions, |addr, size| phys.map(addr, size)).unwrap()
+    };
+    assert_eq!(allocator.bitmap_region(), 0x1000..0x2000);
+    assert_eq!(allocator.free_frames(), 2);
+    allocator.deallocate_frame(0x1000, FrameSize::Small);
+    allocator.deallocate_frame(0x0, FrameSize::Large);
+    allocator.deallocate_frame(0x200000, FrameSize::Small);
+    allocator.deallocate_frame(0x2800, FrameSize::Small);
+    assert_eq!(allocator.free_frames(), 2);
+    // the first frame is below the base address of the bitmap
+    let regions = [0x201000..0x203000, 0x203000..0x204000];
+    let mut allocator = unsafe {
+        BitmapFrameAllocator::from_regions(&regions, |_, size| phys.map(0x8000, size)).unwrap()
+    };
+    allocator.deallocate_frame(0x1ff000, FrameSize::Small);
+    assert_eq!(allocator.free_frames(), 2);
+}
@@ -0,0 +1,210 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::{write_info_with_map, Mem};
+use multiboot::information::{
+    ElfSymbols, MemoryEntry, MemoryType, Module, Multiboot, MultibootInfo, OccupiedRegion,
+    OccupiedRegionKind, PAddr, SymbolType,
+};
+
+#[test]
+/// Without any boot data, the available regions are returned page-aligned.
+fn only_memory_map() {
+    let mut mem = Mem::new(0x20000, 0);
+    write_info_with_map(
+        &mut mem,
+        0x100,
+        &[
+            MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available),
+            MemoryEntry::new(0x9fc00, 0x400, MemoryType::Reserved),
+            MemoryEntry::new(0x100000, 0x100800, MemoryType::Available),
+        ],
+    );
+    let multiboot = unsafe { Multiboot::from_ptr(0x100, &mut mem).unwrap() };
+    let free: Vec<_> = multiboot.free_memory_regions(&[]).unwrap().collect();
+    // the first page contains the information and the memory map
+    assert_eq!(free, vec![0x1000..0x9f000, 0x100000..0x200000]);
+}
+
+#[test]
+/// The kernel, modules, strings and the boot information are all excluded.
+fn boot_data() {
+    let mut mem = Mem::new(0x20000, 0x8000);
+    write_info_with_map(
+        &mut mem,
+        0x1000,
+        &[
+            MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available),
+            MemoryEntry::new(0x100000, 0x1000000, MemoryType::Available),
+            // overlaps the available region above
+            MemoryEntry::new(0x800000, 0x2000, MemoryType::Reserved),
+        ],
+    );
+    {
+        let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+        // all of this is placed at 0x8000 by the allocator
+        multiboot.set_command_line(Some("kernel"));
+        multiboot.set_modules(Some(&[
+            Module::new(0x400000, 0x401234, Some("initrd")),
+            Module::new(0x500000, 0x500800, None),
+        ]));
+        multiboot.set_boot_loader_name(Some("test"));
+    }
+    let multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    // the kernel image and an empty range
+    let reserved = [0x100000..0x180000, 0x180000..0x180000];
+    let free: Vec<_> = multiboot.free_memory_regions(&reserved).unwrap().collect();
+    assert_eq!(
+        free,
+        vec![
+            0x0..0x1000,
+            0x2000..0x8000,
+            0x9000..0x9f000,
+            0x180000..0x400000,
+            0x402000..0x500000,
+            0x501000..0x800000,
+            0x802000..0x1100000,
+        ]
+    );
+}
+
+#[test]
+/// Sections copied by the bootloader are excluded, loaded sections are not.
+fn elf_sections() {
+    let mut mem = Mem::new(0x20000, 0);
+    write_info_with_map(
+        &mut mem,
+        0x1000,
+        &[MemoryEntry::new(0x0, 0x100000, MemoryType::Available)],
+    );
+    let sections: [[u8; 40]; 3] = [
+        [0; 40],
+        // .text: PROGBITS, ALLOC
+        [
+            0x01, 0x00, 0x00, 0x00, // name
+            0x01, 0x00, 0x00, 0x00, // type
+            0x06, 0x00, 0x00, 0x00, // flags
+            0x00, 0x00, 0x01, 0xc0, // addr
+            0x00, 0x10, 0x00, 0x00, // offset
+            0x00, 0x10, 0x00, 0x00, // size
+            0x00, 0x00, 0x00, 0x00, // link
+            0x00, 0x00, 0x00, 0x00, // info
+            0x10, 0x00, 0x00, 0x00, // addralign
+            0x00, 0x00, 0x00, 0x00, // entsize
+        ],
+        // .symtab: SYMTAB
+        [
+            0x07, 0x00, 0x00, 0x00, // name
+            0x02, 0x00, 0x00, 0x00, // type
+            0x00, 0x00, 0x00, 0x00, // flags
+            0x00, 0x00, 0x05, 0x00, // addr
+            0x00, 0x20, 0x00, 0x00, // offset
+            0x00, 0x30, 0x00, 0x00, // size
+            0x00, 0x00, 0x00, 0x00, // link
+            0x00, 0x00, 0x00, 0x00, // info
+            0x04, 0x00, 0x00, 0x00, // addralign
+            0x10, 0x00, 0x00, 0x00, // entsize
+        ],
+    ];
+    for (index, section) in sections.iter().enumerate() {
+        mem.write(0x3000 + index as PAddr * 40, section);
+    }
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_symbols(Some(SymbolType::Elf(ElfSymbols::from_addr(
+        3, 40, 0x3000, 0,
+    ))));
+    let free: Vec<_> = multiboot.free_memory_regions(&[]).unwrap().collect();
+    assert_eq!(
+        free,
+        vec![
+            0x0..0x1000,
+            0x2000..0x3000,
+            0x4000..0x50000,
+            0x53000..0x100000
+        ]
+    );
+}
+
+#[test]
+/// Every structure is reported with its type.
+fn occupied() {
+    let mut mem = Mem::new(0x20000, 0x8000);
+    write_info_with_map(
+        &mut mem,
+        0x1000,
+        &[MemoryEntry::new(0x0, 0x100000, MemoryType::Available)],
+    );
+    // there are no setters for these
+    let flags = 0x40 | 1 << 10 | 1 << 11 | 1 << 12;
+    mem.write(0x1000, &u32::to_le_bytes(flags));
+    mem.write(0x1000 + 68, &u32::to_le_bytes(0x2000)); // apm_table
+    mem.write(0x1000 + 72, &u32::to_le_bytes(0x3000)); // vbe_control_info
+    mem.write(0x1000 + 76, &u32::to_le_bytes(0x3200)); // vbe_mode_info
+    mem.write(0x1000 + 109, &[0]); // framebuffer_type
+    mem.write(0x1000 + 112, &u32::to_le_bytes(0x4000)); // framebuffer_palette_addr
+    mem.write(0x1000 + 116, &u16::to_le_bytes(16)); // framebuffer_palette_num_colors
+    {
+        let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+        multiboot.set_command_line(Some("kernel"));
+        multiboot.set_modules(Some(&[
+            Module::new(0x400000, 0x401234, Some("initrd")),
+            Module::new(0x500000, 0x500800, None),
+        ]));
+        multiboot.set_boot_loader_name(Some("test"));
+    }
+    let multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    let region = |kind, range| OccupiedRegion { kind, range };
+    let occupied: Vec<_> = multiboot.occupied_regions().collect();
+    assert_eq!(
+        occupied,
+        vec![
+            region(OccupiedRegionKind::Info, 0x1000..0x1078),
+            region(OccupiedRegionKind::CommandLine, 0x8000..0x8007),
+            region(OccupiedRegionKind::BootLoaderName, 0x8030..0x8035),
+            region(OccupiedRegionKind::ModuleTable, 0x8008..0x8028),
+            region(OccupiedRegionKind::MemoryMap, 0x1078..0x1090),
+            region(OccupiedRegionKind::ApmTable, 0x2000..0x2014),
+            region(OccupiedRegionKind::VbeControlInfo, 0x3000..0x3200),
+            region(OccupiedRegionKind::VbeModeInfo, 0x3200..0x3300),
+            region(OccupiedRegionKind::Palette, 0x4000..0x4030),
+            region(OccupiedRegionKind::Module(0), 0x400000..0x401234),
+            region(OccupiedRegionKind::ModuleString(0), 0x8028..0x802f),
+            region(OccupiedRegionKind::Module(1), 0x500000..0x500800),
+        ]
+    );
+    assert_eq!(multiboot.find_highest_address(), 0x501000);
+}
+
+#[test]
+/// No memory map means no free memory.
+fn no_memory_map() {
+    let mut mem = Mem::new(0x1000, 0);
+    let mut info = MultibootInfo::default();
+    let multiboot = Multiboot::from_ref(&mut info, &mut mem);
+    assert!(multiboot.free_memory_regions(&[]).is_none());
+}
+
+#[test]
+/// Many reserved ranges give the same result as few.
+fn many_reserved() {
+    let mut mem = Mem::new(0x2000, 0);
+    write_info_with_map(
+        &mut mem,
+        0x100,
+        &[MemoryEntry::new(0x100000, 0x100000, MemoryType::Available)],
+    );
+    let multiboot = unsafe { Multiboot::from_ptr(0x100, &mut mem).unwrap() };
+    // every other page, in descending order
+    let reserved: Vec<_> = (0..100)
+        .rev()
+        .map(|page| 0x100000 + page * 0x2000..0x101000 + page * 0x2000)
+        .collect();
+    let free: Vec<_> = multiboot.free_memory_regions(&reserved).unwrap().collect();
+    let expected: Vec<_> = (0..99)
+        .map(|page| 0x101000 + page * 0x2000..0x102000 + page * 0x2000)
+        .chain(Some(0x1c7000..0x200000))
+        .collect();
+    assert_eq!(free, expected);
+}
@@ -0,0 +1,218 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::{write_info, Mem};
+use multiboot::header::Header;
+use multiboot::information::{
+    AllocationPurpose, AllocationRequest, MemoryAllocator, Module, Multiboot, PAddr,
+    PhysicalMemoryMapper,
+};
+use multiboot::loader::{check_modules, LoadError, ModuleSource};
+
+/// Get a header with the given flags.
+fn header(flags: u32) -> Header {
+    let mut header = [0; 48];
+    header[0..4].copy_from_slice(&0x1BADB002u32.to_le_bytes());
+    header[4..8].copy_from_slice(&flags.to_le_bytes());
+    let checksum = 0u32.wrapping_sub(0x1BADB002).wrapping_sub(flags);
+    header[8..12].copy_from_slice(&checksum.to_le_bytes());
+    Header::from_slice(&header).unwrap()
+}
+
+#[test]
+/// Load modules to page aligned memory.
+fn page_aligned() {
+    let mut mem = Mem::new(0x10000, 0x2001);
+    write_info(&mut mem, 0x1000);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_modules(Some(&[Module::new(0x9000, 0x9100, Some("old"))]));
+
+    let sources = [
+        ModuleSource::new(b"first module", Some("/boot/first arg")),
+        ModuleSource::new(b"second", None),
+        ModuleSource::new(b"", Some("empty")),
+    ];
+    multiboot
+        .load_modules(&header(1), 0x100000..0x200000, &sources)
+        .unwrap();
+    let modules: Vec<Module> = multiboot.modules().unwrap().collect();
+    assert_eq!(modules.len(), 3);
+    for (module, source) in modules.iter().zip(sources.iter()) {
+        assert_eq!(module.start % 4096, 0);
+        assert_eq!(module.string, source.string);
+        assert_eq!(multiboot.module_data(module), Some(source.data));
+    }
+    assert!(multiboot.module_by_name("old").is_none());
+}
+
+#[test]
+/// Without the flag, modules are placed anywhere.
+fn unaligned() {
+    let mut mem = Mem::new(0x10000, 0x2001);
+    write_info(&mut mem, 0x1000);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    let sources = [
+        ModuleSource::new(b"abc", None),
+        ModuleSource::new(b"def", None),
+    ];
+    multiboot
+        .load_modules(&header(0), 0x100000..0x200000, &sources)
+        .unwrap();
+    let modules: Vec<Module> = multiboot.modules().unwrap().collect();
+    assert_eq!(modules[0].start, 0x2008);
+    assert_eq!(multiboot.module_data(&modules[1]), Some(&b"def"[..]));
+}
+
+#[test]
+/// Loading fails if a module ends up inside the kernel.
+fn overlaps_kernel() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    let sources = [
+        ModuleSource::new(b"outside", None),
+        ModuleSource::new(b"inside", None),
+    ];
+    assert_eq!(
+        multiboot.load_modules(&header(1), 0x3000..0x4000, &sources),
+        Err(LoadError::OverlapsKernel(1))
+    );
+    assert!(multiboot.modules().is_none());
+    assert_eq!(
+        multiboot.load_modules(&header(1), 0..0, &[ModuleSource::new(&[0; 0x10000], None)]),
+        Err(LoadError::AllocationFailed(0))
+    );
+    assert!(multiboot.modules().is_none());
+    assert!(mem.freed.contains(&0x2000));
+    assert!(mem.freed.contains(&0x3000));
+}
+
+#[test]
+/// Check modules that were placed elsewhere.
+fn check() {
+    let kernel = 0x100000..0x180000;
+    let ok = [
+        Module::new(0x200000, 0x201000, None),
+        Module::new(0x201000, 0x201800, None),
+        Module::new(0x100000, 0x100000, None),
+    ];
+    assert_eq!(
+        check_modules(ok.iter().cloned(), kernel.clone(), true),
+        Ok(())
+    );
+    let check = |modules: &[Module], page_aligned| {
+        check_modules(modules.iter().cloned(), kernel.clone(), page_aligned)
+    };
+    assert_eq!(
+        check(&[Module::new(0x200800, 0x201000, None)], true),
+        Err(LoadError::Misaligned(0))
+    );
+    assert_eq!(
+        check(&[Module::new(0x200800, 0x201000, None)], false),
+        Ok(())
+    );
+    assert_eq!(
+        check(
+            &[
+                Module::new(0x200000, 0x202000, None),
+                Module::new(0x300000, 0x301000, None),
+                Module::new(0x201000, 0x203000, None),
+            ],
+            true
+        ),
+        Err(LoadError::Overlap(0, 2))
+    );
+    assert_eq!(
+        check(&[Module::new(0xff000, 0x101000, None)], true),
+        Err(LoadError::OverlapsKernel(0))
+    );
+    assert_eq!(
+        check(&[Module::new(0x201000, 0x200000, None)], true),
+        Err(LoadError::InvalidRange(0))
+    );
+    assert_eq!(
+        check(&[Module::new(0xffff_f000, 0x1_0000_1000, None)], true),
+        Err(LoadError::AddressTooLarge(0))
+    );
+}
+
+/// Records the requests and sized deallocations passed to `Mem`.
+struct Recorder {
+    mem: Mem,
+    requests: Vec<AllocationRequest>,
+    freed: Vec<(PAddr, usize)>,
+}
+
+impl PhysicalMemoryMapper for Recorder {
+    unsafe fn paddr_to_slice(&self, addr: PAddr, size: usize) -> Option<&'static [u8]> {
+        self.mem.paddr_to_slice(addr, size)
+    }
+}
+
+impl MemoryAllocator for Recorder {
+    unsafe fn allocate(&mut self, length: usize) -> Option<(PAddr, &mut [u8])> {
+        self.mem.allocate(length)
+    }
+
+    unsafe fn allocate_request(
+        &mut self,
+        request: AllocationRequest,
+    ) -> Option<(PAddr, &mut [u8])> {
+        self.requests.push(request);
+        self.mem.allocate_request(request)
+    }
+
+    unsafe fn deallocate(&mut self, _addr: PAddr) {
+        unimplemented!()
+    }
+
+    unsafe fn deallocate_sized(&mut self, addr: PAddr, size: usize) {
+        self.freed.push((addr, size));
+    }
+}
+
+#[test]
+/// Allocations describe what they are for and deallocations know their size.
+fn requests() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let mut recorder = Recorder {
+        mem,
+        requests: Vec::new(),
+        freed: Vec::new(),
+    };
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut recorder).unwrap() };
+    multiboot.set_command_line(Some("first"));
+    multiboot.set_command_line(Some("second"));
+    multiboot
+        .load_modules(
+            &header(1),
+            0x100000..0x200000,
+            &[ModuleSource::new(b"data", None)],
+        )
+        .unwrap();
+
+    let requests: Vec<(usize, usize, AllocationPurpose)> = recorder
+        .requests
+        .iter()
+        .map(|r| (r.size, r.align, r.purpose))
+        .collect();
+    assert_eq!(
+        requests,
+        [
+            (6, 1, AllocationPurpose::String),
+            (7, 1, AllocationPurpose::String),
+            (4, 4096, AllocationPurpose::Module),
+            (16, 4, AllocationPurpose::ModuleTable),
+        ]
+    );
+    assert!(recorder.requests.iter().all(|r| r.max_address == 1 << 32));
+    assert_eq!(recorder.freed, [(0x2000, 6)]);
+
+    // the default implementation checks the maximum address
+    let request = AllocationRequest::new(16, AllocationPurpose::MemoryMap).max_address(0x3000);
+    assert!(unsafe { recorder.mem.allocate_request(request) }.is_none());
+    assert_eq!(recorder.mem.freed.len(), 1);
+}
@@ -0,0 +1,439 @@
+extern crate core;
+extern crate multiboot;
+
+use core::mem;
+
+mod common;
+
+use common::{write_info, write_info_with_map, Mem};
+use multiboot::e820::{self, E820Entry, E820Iter};
+use multiboot::information::{
+    memory_bounds, ExtendedMemoryEntry, MemoryBoundsMismatch, MemoryEntry, MemoryType, Multiboot,
+    PAddr, PhysicalMemoryMapper,
+};
+use multiboot::uefi::{self, MemoryDescriptor, MemoryDescriptorIter};
+
+#[test]
+/// Types without their own variant are preserved.
+fn unknown_types() {
+    let mut mem = Mem::new(0x4000, 0x3000);
+    write_info(&mut mem, 0x1000);
+    let regions = [
+        MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available),
+        MemoryEntry::new(0x100000, 0x100000, MemoryType::Persistent),
+        MemoryEntry::new(0x200000, 0x100000, MemoryType::Disabled),
+        MemoryEntry::new(0x300000, 0x100000, MemoryType::Other(12)),
+        // this is normalized
+        MemoryEntry::new(0x400000, 0x100000, MemoryType::Other(2)),
+    ];
+    for (index, region) in regions.iter().enumerate() {
+        let region: [u8; 24] = unsafe { mem::transmute::<MemoryEntry, [u8; 24]>(*region) };
+        mem.write(0x2000 + index as PAddr * 24, &region);
+    }
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_memory_regions(Some((0x2000, regions.len())));
+    let types: Vec<_> = multiboot
+        .memory_regions()
+        .unwrap()
+        .map(|region| region.memory_type())
+        .collect();
+    assert_eq!(
+        types,
+        vec![
+            MemoryType::Available,
+            MemoryType::Persistent,
+            MemoryType::Disabled,
+            MemoryType::Other(12),
+            MemoryType::Reserved,
+        ]
+    );
+    // plain entries don't have extended attributes
+    let attributes: Vec<_> = multiboot
+        .memory_regions()
+        .unwrap()
+        .with_extended_attributes()
+        .map(|(_, attributes)| attributes)
+        .collect();
+    assert_eq!(attributes, vec![None; 5]);
+}
+
+#[test]
+/// Read entries that contain extended attributes.
+fn extended_attributes() {
+    let mut mem = Mem::new(0x4000, 0x3000);
+    write_info(&mut mem, 0x1000);
+    let regions = [
+        MemoryEntry::with_extended_attributes(0x0, 0x9fc00, MemoryType::Available, 1),
+        MemoryEntry::with_extended_attributes(0x100000, 0x100000, MemoryType::Persistent, 3),
+        MemoryEntry::with_extended_attributes(0x200000, 0x100000, MemoryType::Available, 0),
+    ];
+    for (index, region) in regions.iter().enumerate() {
+        let region: [u8; 28] = unsafe { mem::transmute::<ExtendedMemoryEntry, [u8; 28]>(*region) };
+        mem.write(0x2000 + index as PAddr * 28, &region);
+    }
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_extended_memory_regions(Some((0x2000, regions.len())));
+    let entries: Vec<_> = multiboot
+        .memory_regions()
+        .unwrap()
+        .with_extended_attributes()
+        .map(|(entry, attributes)| (entry.base_address(), entry.size(), attributes))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (0x0, 24, Some(1)),
+            (0x100000, 24, Some(3)),
+            (0x200000, 24, Some(0)),
+        ]
+    );
+}
+
+#[test]
+/// Copy a map that is given as bytes.
+fn copy_raw_map() {
+    let mut mem = Mem::new(0x4000, 0x3000);
+    write_info(&mut mem, 0x1000);
+    let regions = [
+        MemoryEntry::with_extended_attributes(0x0, 0x9fc00, MemoryType::Available, 1),
+        MemoryEntry::with_extended_attributes(0x100000, 0x100000, MemoryType::Reserved, 3),
+    ];
+    let mut map = Vec::new();
+    for region in regions.iter() {
+        let region: [u8; 28] = unsafe { mem::transmute::<ExtendedMemoryEntry, [u8; 28]>(*region) };
+        map.extend_from_slice(&region);
+    }
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.try_copy_memory_map(Some(&map)).unwrap();
+    let entries: Vec<_> = multiboot
+        .memory_regions()
+        .unwrap()
+        .with_extended_attributes()
+        .map(|(entry, attributes)| (entry.base_address(), attributes))
+        .collect();
+    assert_eq!(entries, vec![(0x0, Some(1)), (0x100000, Some(3))]);
+    // the same map again, but in place
+    multiboot
+        .try_set_raw_memory_map(Some((0x3000, 1)), 28)
+        .unwrap();
+    assert_eq!(multiboot.memory_regions().unwrap().count(), 1);
+    multiboot.try_copy_memory_map(None).unwrap();
+    assert!(multiboot.memory_regions().is_none());
+    // the copy is only freed once it isn't used anymore
+    assert_eq!(mem.freed, [0x3000]);
+}
+
+/// Memory that can't allocate.
+struct Mapper(Mem);
+
+impl PhysicalMemoryMapper for Mapper {
+    unsafe fn paddr_to_slice(&self, addr: PAddr, size: usize) -> Option<&'static [u8]> {
+        self.0.paddr_to_slice(addr, size)
+    }
+}
+
+#[test]
+/// Publishing a map that is already in memory doesn't need an allocator.
+fn mapper_only() {
+    let mut mem = Mem::new(0x4000, 0x3000);
+    write_info_with_map(
+        &mut mem,
+        0x1000,
+        &[MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available)],
+    );
+    let mut mapper = Mapper(mem);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mapper).unwrap() };
+    multiboot.set_memory_regions(None);
+    assert!(multiboot.memory_regions().is_none());
+    multiboot.set_memory_regions(Some((0x1078, 1)));
+    assert_eq!(multiboot.memory_regions().unwrap().count(), 1);
+    multiboot.set_framebuffer_table(None);
+    assert!(multiboot.framebuffer_table().is_none());
+}
+
+/// A map as returned by the BIOS, 24 bytes per entry.
+const E820_MAP: [u8; 72] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // length
+    0x01, 0x00, 0x00, 0x00, // type
+    0x01, 0x00, 0x00, 0x00, // extended attributes
+    0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // base
+    0x00, 0x00, 0xf0, 0x07, 0x00, 0x00, 0x00, 0x00, // length
+    0x01, 0x00, 0x00, 0x00, // type
+    0x01, 0x00, 0x00, 0x00, // extended attributes
+    0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, // base
+    0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, // length
+    0x0c, 0x00, 0x00, 0x00, // type
+    0x03, 0x00, 0x00, 0x00, // extended attributes
+];
+
+#[test]
+/// Parse E820 maps with both entry sizes.
+fn e820_parse() {
+    let entries: Vec<_> = E820Iter::new(&E820_MAP, 24).unwrap().collect();
+    assert_eq!(
+        entries,
+        vec![
+            E820Entry {
+                extended_attributes: Some(1),
+                ..E820Entry::new(0x0, 0x9fc00, MemoryType::Available)
+            },
+            E820Entry {
+                extended_attributes: Some(1),
+                ..E820Entry::new(0x100000, 0x7f00000, MemoryType::Available)
+            },
+            E820Entry {
+                extended_attributes: Some(3),
+                ..E820Entry::new(0x100000000, 0x10000000, MemoryType::Other(12))
+            },
+        ]
+    );
+    let mut short = [0; 60];
+    assert_eq!(e820::write_e820(entries.clone(), 20, &mut short), Some(3));
+    let short_entries: Vec<_> = E820Iter::new(&short, 20).unwrap().collect();
+    assert_eq!(
+        short_entries[2],
+        E820Entry::new(0x100000000, 0x10000000, MemoryType::Other(12))
+    );
+    // writing them with the original size gives the same bytes
+    let mut long = [0; 72];
+    assert_eq!(e820::write_e820(entries, 24, &mut long), Some(3));
+    assert_eq!(long, E820_MAP);
+    assert_eq!(e820::write_e820(short_entries, 24, &mut long[..48]), None);
+    assert!(E820Iter::new(&short, 16).is_none());
+}
+
+#[test]
+/// Converting an E820 map to a Multiboot map and back is lossless.
+fn e820_round_trip() {
+    let mut mem = Mem::new(0x4000, 0x3000);
+    write_info(&mut mem, 0x1000);
+    let mut entries: Vec<_> = E820Iter::new(&E820_MAP, 24).unwrap().collect();
+    // mix in an entry without extended attributes
+    entries.push(E820Entry::new(0x9fc00, 0x400, MemoryType::Reserved));
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_e820_memory_map(Some(&entries));
+    let sizes: Vec<_> = multiboot
+        .memory_regions()
+        .unwrap()
+        .map(|entry| entry.size())
+        .collect();
+    assert_eq!(sizes, vec![24, 24, 24, 20]);
+    let read: Vec<E820Entry> = multiboot
+        .memory_regions()
+        .unwrap()
+        .with_extended_attributes()
+        .map(E820Entry::from)
+        .collect();
+    assert_eq!(read, entries);
+    // plain entries convert as well
+    let plain: Vec<E820Entry> = multiboot
+        .memory_regions()
+        .unwrap()
+        .map(E820Entry::from)
+        .collect();
+    assert_eq!(plain[3], entries[3]);
+    assert_eq!(plain[0].extended_attributes, None);
+}
+
+#[test]
+/// Write a Multiboot map into a buffer.
+fn e820_write_memory_map() {
+    let entries = [
+        E820Entry::new(0x0, 0x9fc00, MemoryType::Available),
+        E820Entry {
+            extended_attributes: Some(1),
+            ..E820Entry::new(0x100000, 0x7f00000, MemoryType::Available)
+        },
+    ];
+    assert_eq!(e820::memory_map_size(entries.iter().cloned()), 52);
+    let mut buffer = [0; 52];
+    assert_eq!(
+        e820::write_memory_map(entries.iter().cloned(), &mut buffer[..51]),
+        None
+    );
+    assert_eq!(
+        e820::write_memory_map(entries.iter().cloned(), &mut buffer),
+        Some(52)
+    );
+    let expected = MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available);
+    assert_eq!(buffer[..24], unsafe {
+        mem::transmute::<MemoryEntry, [u8; 24]>(expected)
+    });
+    let expected =
+        MemoryEntry::with_extended_attributes(0x100000, 0x7f00000, MemoryType::Available, 1);
+    assert_eq!(buffer[24..], unsafe {
+        mem::transmute::<ExtendedMemoryEntry, [u8; 28]>(expected)
+    });
+    assert_eq!(entries[0].to_memory_entry().size(), 20);
+}
+
+/// Write UEFI memory descriptors with a stride of 48 bytes.
+fn uefi_map(descriptors: &[(u32, u64, u64)]) -> Vec<u8> {
+    let mut bytes = vec![0xaa; descriptors.len() * 48];
+    for (&(ty, start, pages), bytes) in descriptors.iter().zip(bytes.chunks_mut(48)) {
+        bytes[0..4].copy_from_slice(&ty.to_le_bytes());
+        bytes[8..16].copy_from_slice(&start.to_le_bytes());
+        bytes[16..24].copy_from_slice(&0u64.to_le_bytes());
+        bytes[24..32].copy_from_slice(&pages.to_le_bytes());
+        bytes[32..40].copy_from_slice(&uefi::EFI_MEMORY_WB.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+/// Convert a UEFI memory map the way GRUB does.
+fn uefi_conversion() {
+    let bytes = uefi_map(&[
+        (uefi::EFI_BOOT_SERVICES_CODE, 0x0, 0x1),
+        (uefi::EFI_CONVENTIONAL_MEMORY, 0x1000, 0x9e),
+        (uefi::EFI_RESERVED_MEMORY_TYPE, 0x9f000, 0x1),
+        // not sorted
+        (uefi::EFI_ACPI_RECLAIM_MEMORY, 0x7fe0000, 0x10),
+        (uefi::EFI_LOADER_DATA, 0x100000, 0x100),
+        (uefi::EFI_CONVENTIONAL_MEMORY, 0x200000, 0x7de0),
+        (uefi::EFI_ACPI_MEMORY_NVS, 0x7ff0000, 0x10),
+        (uefi::EFI_RUNTIME_SERVICES_DATA, 0x8000000, 0x0),
+        (uefi::EFI_MEMORY_MAPPED_IO, 0xfec00000, 0x1),
+        (uefi::EFI_UNUSABLE_MEMORY, 0x100000000, 0x1),
+        (uefi::EFI_PERSISTENT_MEMORY, 0x200000000, 0x100),
+        (0x70000000, 0x300000000, 0x1),
+    ]);
+    let descriptors = MemoryDescriptorIter::new(&bytes, 48).unwrap();
+    assert_eq!(
+        descriptors.clone().nth(1),
+        Some(MemoryDescriptor {
+            ty: uefi::EFI_CONVENTIONAL_MEMORY,
+            physical_start: 0x1000,
+            virtual_start: 0,
+            number_of_pages: 0x9e,
+            attribute: uefi::EFI_MEMORY_WB,
+        })
+    );
+    let mut entries = [MemoryEntry::default(); 16];
+    let count = uefi::convert_memory_map(descriptors.clone(), &mut entries).unwrap();
+    let entries: Vec<_> = entries[..count]
+        .iter()
+        .map(|e| (e.base_address(), e.length(), e.memory_type()))
+        .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (0x0, 0x9f000, MemoryType::Available),
+            (0x9f000, 0x1000, MemoryType::Reserved),
+            (0x100000, 0x7ee0000, MemoryType::Available),
+            (0x7fe0000, 0x10000, MemoryType::ACPI),
+            (0x7ff0000, 0x10000, MemoryType::NVS),
+            (0xfec00000, 0x1000, MemoryType::Reserved),
+            (0x100000000, 0x1000, MemoryType::Defect),
+            (0x200000000, 0x100000, MemoryType::Persistent),
+            (0x300000000, 0x1000, MemoryType::Reserved),
+        ]
+    );
+    // the merged map is smaller than the number of descriptors
+    let mut entries = [MemoryEntry::default(); 9];
+    assert_eq!(
+        uefi::convert_memory_map(descriptors.clone(), &mut entries),
+        Some(9)
+    );
+    let mut entries = [MemoryEntry::default(); 8];
+    assert_eq!(uefi::convert_memory_map(descriptors, &mut entries), None);
+    assert!(MemoryDescriptorIter::new(&bytes, 32).is_none());
+}
+
+#[test]
+/// The converted map can be published using the setters.
+fn uefi_publish() {
+    let mut mem = Mem::new(0x4000, 0x3000);
+    write_info(&mut mem, 0x1000);
+    let bytes = uefi_map(&[
+        (uefi::EFI_CONVENTIONAL_MEMORY, 0x0, 0x9f),
+        (uefi::EFI_CONVENTIONAL_MEMORY, 0x100000, 0x100),
+    ]);
+    let descriptors = MemoryDescriptorIter::new(&bytes, 48).unwrap();
+    let mut entries = [MemoryEntry::default(); 4];
+    let count = uefi::convert_memory_map(descriptors, &mut entries).unwrap();
+    for (index, entry) in entries[..count].iter().enumerate() {
+        let entry: [u8; 24] = unsafe { mem::transmute::<MemoryEntry, [u8; 24]>(*entry) };
+        mem.write(0x2000 + index as PAddr * 24, &entry);
+    }
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_memory_regions(Some((0x2000, count)));
+    let regions: Vec<_> = multiboot
+        .memory_regions()
+        .unwrap()
+        .map(|e| (e.base_address(), e.length()))
+        .collect();
+    assert_eq!(regions, vec![(0x0, 0x9f000), (0x100000, 0x100000)]);
+}
+
+#[test]
+/// Compute the memory bounds from different maps.
+fn bounds() {
+    let regions = [
+        MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available),
+        MemoryEntry::new(0x9fc00, 0x400, MemoryType::Reserved),
+        MemoryEntry::new(0xf0000, 0x10000, MemoryType::Reserved),
+        MemoryEntry::new(0x100000, 0x7ee0000, MemoryType::Available),
+        MemoryEntry::new(0x100000000, 0x40000000, MemoryType::Available),
+    ];
+    assert_eq!(memory_bounds(&regions), (639, 0x7ee0000 / 1024));
+    // unsorted, split and overlapping entries
+    let regions = [
+        MemoryEntry::new(0x200000, 0x100000, MemoryType::Available),
+        MemoryEntry::new(0x80000, 0x40000, MemoryType::Available),
+        MemoryEntry::new(0x0, 0x80000, MemoryType::Available),
+        MemoryEntry::new(0x100000, 0x180000, MemoryType::Available),
+        MemoryEntry::new(0x280000, 0x1000, MemoryType::Reserved),
+    ];
+    assert_eq!(memory_bounds(&regions), (640, 0x180000 / 1024));
+    // holes at the very start
+    let regions = [
+        MemoryEntry::new(0x0, 0x100000, MemoryType::Available),
+        MemoryEntry::new(0x0, 0x1000, MemoryType::Reserved),
+        MemoryEntry::new(0x100000, 0x100000, MemoryType::ACPI),
+    ];
+    assert_eq!(memory_bounds(&regions), (0, 0));
+    assert_eq!(memory_bounds(&[]), (0, 0));
+}
+
+#[test]
+/// Set the map and the bounds at once and check existing bounds.
+fn set_and_check_bounds() {
+    let mut mem = Mem::new(0x4000, 0x3000);
+    write_info(&mut mem, 0x1000);
+    let regions = [
+        MemoryEntry::new(0x0, 0x9fc00, MemoryType::Available),
+        MemoryEntry::new(0x9fc00, 0x400, MemoryType::Reserved),
+        MemoryEntry::new(0x100000, 0x100000, MemoryType::Available),
+    ];
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    assert_eq!(multiboot.check_memory_bounds(), Ok(()));
+    multiboot.set_memory_map_and_bounds(Some(&regions));
+    assert_eq!(multiboot.lower_memory_bound(), Some(639));
+    assert_eq!(multiboot.upper_memory_bound(), Some(1024));
+    let read: Vec<_> = multiboot
+        .memory_regions()
+        .unwrap()
+        .map(|e| (e.base_address(), e.length(), e.memory_type()))
+        .collect();
+    assert_eq!(read.len(), 3);
+    assert_eq!(read[2], (0x100000, 0x100000, MemoryType::Available));
+    assert_eq!(multiboot.check_memory_bounds(), Ok(()));
+
+    // smaller bounds are fine, larger ones are not
+    multiboot.set_memory_bounds(Some((600, 1000)));
+    assert_eq!(multiboot.check_memory_bounds(), Ok(()));
+    multiboot.set_memory_bounds(Some((639, 2048)));
+    assert_eq!(
+        multiboot.check_memory_bounds(),
+        Err(MemoryBoundsMismatch {
+            bounds: (639, 2048),
+            memory_map: (639, 1024),
+        })
+    );
+
+    multiboot.set_memory_map_and_bounds(None);
+    assert!(multiboot.memory_regions().is_none());
+    assert_eq!(multiboot.lower_memory_bound(), None);
+}
@@ -0,0 +1,449 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::{write_info, Mem};
+use multiboot::information::{
+    MemoryEntry, MemoryType, Module, Multiboot, MultibootInfoBuilder, OccupiedRegionKind, PAddr,
+    SetError,
+};
+use multiboot::module_kind::{ElfClass, ModuleKind, EM_386, EM_AARCH64, EM_X86_64};
+
+#[test]
+/// Find modules by name and read their contents.
+fn by_name() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    mem.write(0x8000, b"initrd contents");
+    mem.write(0x9000, b"config");
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    assert!(multiboot.module_by_name("initrd").is_none());
+    multiboot.set_modules(Some(&[
+        Module::new(0x8000, 0x800f, Some("/boot/initrd.cpio root=/dev/ram0 ro")),
+        Module::new(0x9000, 0x9006, Some("\"/boot/my config\"")),
+        Module::new(0xa000, 0xa000, None),
+        Module::new(0xa000, 0x20000, Some("outside")),
+    ]));
+
+    let initrd = multiboot.module_by_name("initrd.cpio").unwrap();
+    assert_eq!(initrd.start, 0x8000);
+    assert_eq!(initrd.path().unwrap(), "/boot/initrd.cpio");
+    assert_eq!(initrd.arguments(), Some("root=/dev/ram0 ro"));
+    assert!(initrd.command_line().unwrap().flag("ro"));
+    assert_eq!(
+        multiboot.module_data(&initrd),
+        Some(&b"initrd contents"[..])
+    );
+    assert!(multiboot.module_by_name("/boot/initrd.cpio").is_some());
+    assert!(multiboot
+        .module_by_name("/boot/initrd.cpio root=/dev/ram0 ro")
+        .is_some());
+    assert!(multiboot.module_by_name("initrd").is_none());
+    assert!(multiboot.module_by_name("").is_none());
+    assert!(multiboot.module_by_name("boot/initrd.cpio").is_none());
+
+    let config = multiboot.module_by_name("my config").unwrap();
+    assert_eq!(config.arguments(), Some(""));
+    assert_eq!(multiboot.module_data(&config), Some(&b"config"[..]));
+
+    let modules: Vec<_> = multiboot.modules().unwrap().collect();
+    assert!(modules[2].path().is_none());
+    assert_eq!(multiboot.module_data(&modules[2]), Some(&b""[..]));
+    assert_eq!(multiboot.module_data(&modules[3]), None);
+    assert_eq!(
+        multiboot.module_data(&Module::new(0x9000, 0x8000, None)),
+        None
+    );
+}
+
+#[test]
+/// Recognize the contents of modules.
+fn detect_kind() {
+    let mut elf32 = [0u8; 64];
+    elf32[..7].copy_from_slice(b"\x7fELF\x01\x01\x01");
+    elf32[18..20].copy_from_slice(&EM_386.to_le_bytes());
+    let mut elf64 = elf32;
+    elf64[4] = 2;
+    elf64[18..20].copy_from_slice(&EM_X86_64.to_le_bytes());
+    let mut elf64_be = elf64;
+    elf64_be[5] = 2;
+    elf64_be[18..20].copy_from_slice(&EM_AARCH64.to_be_bytes());
+    let mut bmp = [0u8; 54];
+    bmp[..2].copy_from_slice(b"BM");
+    bmp[14] = 40;
+    let mut tar = [0u8; 512];
+    tar[257..263].copy_from_slice(b"ustar\0");
+
+    let cases: [(&[u8], ModuleKind); 14] = [
+        (
+            &elf32,
+            ModuleKind::Elf {
+                class: ElfClass::Elf32,
+                machine: EM_386,
+            },
+        ),
+        (
+            &elf64,
+            ModuleKind::Elf {
+                class: ElfClass::Elf64,
+                machine: EM_X86_64,
+            },
+        ),
+        (
+            &elf64_be,
+            ModuleKind::Elf {
+                class: ElfClass::Elf64,
+                machine: EM_AARCH64,
+            },
+        ),
+        (&[0x1f, 0x8b, 0x08, 0x00], ModuleKind::Gzip),
+        (b"\xfd7zXZ\x00\x00", ModuleKind::Xz),
+        (&[0x28, 0xb5, 0x2f, 0xfd, 0x00], ModuleKind::Zstd),
+        (b"07070100000001", ModuleKind::Cpio),
+        (b"070707000001", ModuleKind::Cpio),
+        (&tar, ModuleKind::Tar),
+        (&[0x36, 0x04, 0x00, 0x10], ModuleKind::Psf { version: 1 }),
+        (&[0x72, 0xb5, 0x4a, 0x86], ModuleKind::Psf { version: 2 }),
+        (&bmp, ModuleKind::Bmp),
+        (b"BM is not enough", ModuleKind::Unknown),
+        (b"\x7fELF", ModuleKind::Unknown),
+    ];
+    for &(data, kind) in cases.iter() {
+        assert_eq!(ModuleKind::detect(data), kind, "{:x?}", &data[..4]);
+    }
+    assert!(ModuleKind::Gzip.is_compressed());
+    assert!(!ModuleKind::Tar.is_compressed());
+    assert_eq!(ModuleKind::detect(&[]), ModuleKind::Unknown);
+
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    mem.write(0x8000, &elf32);
+    mem.write(0x9000, &[0x1f, 0x8b, 0x08, 0x00]);
+    mem.write(0xf000, &[0x1f, 0x8b, 0x08, 0x00]);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_modules(Some(&[
+        Module::new(0x8000, 0x8040, Some("kernel64")),
+        Module::new(0x9000, 0x9004, Some("initrd.gz")),
+        Module::new(0xa000, 0x20000, Some("outside")),
+        // only the start of this is mapped
+        Module::new(0xf000, 0x100000, Some("huge.gz")),
+    ]));
+    let kinds: Vec<_> = multiboot
+        .modules()
+        .unwrap()
+        .map(|module| multiboot.module_kind(&module))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ModuleKind::Elf {
+                class: ElfClass::Elf32,
+                machine: EM_386
+            },
+            ModuleKind::Gzip,
+            ModuleKind::Unknown,
+            ModuleKind::Gzip,
+        ]
+    );
+    // only modules from the table are accessed
+    let unknown = Module::new(0x8000, 0x8010, None);
+    assert_eq!(multiboot.module_kind(&unknown), ModuleKind::Unknown);
+    assert!(multiboot.module_data(&unknown).is_none());
+}
+
+/// Get the names of all modules.
+fn names<'a>(multiboot: &'a Multiboot<Mem>) -> Vec<Option<&'a str>> {
+    multiboot.modules().unwrap().map(|m| m.string).collect()
+}
+
+/// Get the address of the module table.
+fn module_table(multiboot: &Multiboot<Mem>) -> PAddr {
+    let region = multiboot
+        .occupied_regions()
+        .find(|region| region.kind == OccupiedRegionKind::ModuleTable);
+    region.unwrap().range.start
+}
+
+#[test]
+/// Add and remove modules one by one.
+fn edit() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let base = mem.memory.as_ptr() as usize;
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.push_module(Module::new(0x8000, 0x9000, Some("b")));
+    multiboot.insert_module(0, Module::new(0x9000, 0xa000, Some("a")));
+    multiboot.push_module(Module::new(0xa000, 0xb000, None));
+    multiboot.insert_module(3, Module::new(0xb000, 0xc000, Some("d")));
+    assert_eq!(names(&multiboot), [Some("a"), Some("b"), None, Some("d")]);
+
+    let b = multiboot.module_by_name("b").unwrap();
+    let b_string = (b.string.unwrap().as_ptr() as usize - base) as PAddr;
+    assert_eq!(multiboot.remove_module(1), 0x8000..0x9000);
+    assert_eq!(names(&multiboot), [Some("a"), None, Some("d")]);
+    assert_eq!(multiboot.module_by_name("a").unwrap().start, 0x9000);
+
+    multiboot.retain_modules(|m| m.string.is_some());
+    assert_eq!(names(&multiboot), [Some("a"), Some("d")]);
+    let mut calls = 0;
+    multiboot.retain_modules(|_| {
+        calls += 1;
+        false
+    });
+    assert_eq!(calls, 2);
+    assert!(multiboot.modules().is_none());
+    multiboot.retain_modules(|_| panic!("there are no modules"));
+
+    // the string of "b" is only freed once
+    assert_eq!(
+        mem.freed.iter().filter(|&&addr| addr == b_string).count(),
+        1
+    );
+}
+
+#[test]
+#[should_panic]
+/// Removing a module that doesn't exist panics.
+fn remove_out_of_bounds() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.push_module(Module::new(0x8000, 0x9000, None));
+    multiboot.remove_module(1);
+}
+
+#[test]
+/// Failing to edit the table leaves it unchanged.
+fn edit_failure() {
+    // there is only room for one table with one module
+    let mut mem = Mem::new(0x2018, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.push_module(Module::new(0x8000, 0x9000, None));
+    assert_eq!(
+        multiboot.try_push_module(Module::new(0x9000, 0xa000, None)),
+        Err(SetError::AllocationFailed)
+    );
+    assert_eq!(
+        multiboot.try_insert_module(0, Module::new(0x1_0000_0000, 0x1_0000_1000, None)),
+        Err(SetError::AddressTooLarge)
+    );
+    assert_eq!(
+        multiboot.try_retain_modules(|_| panic!("nothing to keep")),
+        Err(SetError::AllocationFailed)
+    );
+    let modules: Vec<Module> = multiboot.modules().unwrap().collect();
+    assert_eq!(modules, [Module::new(0x8000, 0x9000, None)]);
+
+    // a table that was passed in stays in place if its strings can't be copied
+    let mut mem = Mem::new(0x2020, 0x2000);
+    let modules = [Module::new(0x8000, 0x9000, Some("/boot/initrd"))];
+    MultibootInfoBuilder::new()
+        .modules(&modules)
+        .build(&mut mem.memory[0x1000..0x2000], 0x1000)
+        .unwrap();
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    let table = module_table(&multiboot);
+    for _ in 0..2 {
+        assert_eq!(
+            multiboot.try_push_module(Module::new(0x9000, 0xa000, None)),
+            Err(SetError::AllocationFailed)
+        );
+        assert_eq!(
+            multiboot.try_retain_modules(|_| panic!("nothing to keep")),
+            Err(SetError::AllocationFailed)
+        );
+    }
+    assert_eq!(module_table(&multiboot), table);
+    assert_eq!(names(&multiboot), [Some("/boot/initrd")]);
+}
+
+#[test]
+/// Strings with a zero byte inside can't be copied.
+fn interior_nul() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.push_module(Module::new(0x8000, 0x9000, Some("/boot/initrd")));
+    let module = Module::new(0x9000, 0xa000, Some("a\0bcdef"));
+    assert_eq!(
+        multiboot.try_set_modules(Some(&[module])),
+        Err(SetError::InteriorNul)
+    );
+    assert_eq!(
+        multiboot.try_push_module(module),
+        Err(SetError::InteriorNul)
+    );
+    assert_eq!(names(&multiboot), [Some("/boot/initrd")]);
+    multiboot.set_modules(None);
+    // the table allocated by try_set_modules, the string and the table
+    assert_eq!(mem.freed.len(), 3);
+}
+
+#[test]
+/// Strings that aren't UTF-8 are only available as bytes.
+fn latin1_strings() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let cmdline = {
+        let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+        multiboot.set_command_line(Some("placeholder"));
+        multiboot.push_module(Module::from_bytes(0x8000, 0x9000, Some(b"caf\xe9")));
+        multiboot.push_module(Module::new(0x9000, 0xa000, Some("caf\u{e9}")));
+
+        let modules: Vec<Module> = multiboot.modules().unwrap().collect();
+        assert_eq!(modules[0].string, None);
+        assert_eq!(modules[0].string_bytes(), Some(&b"caf\xe9"[..]));
+        assert_eq!(modules[1].string, Some("caf\u{e9}"));
+        assert_eq!(modules[1].string_bytes(), Some("caf\u{e9}".as_bytes()));
+
+        // the raw string survives editing the table
+        multiboot.remove_module(1);
+        let module = multiboot.modules().unwrap().next().unwrap();
+        assert_eq!(module.string_bytes(), Some(&b"caf\xe9"[..]));
+        assert!(!module.has_name("caf\u{e9}"));
+
+        assert_eq!(multiboot.command_line(), Some("placeholder"));
+        assert_eq!(multiboot.boot_loader_name_bytes(), None);
+        multiboot
+            .occupied_regions()
+            .find(|region| region.kind == OccupiedRegionKind::CommandLine)
+            .unwrap()
+            .range
+            .start
+    };
+    mem.write(cmdline, &[0xff]);
+    let multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    assert_eq!(multiboot.command_line(), None);
+    assert_eq!(multiboot.command_line_bytes(), Some(&b"\xfflaceholder"[..]));
+}
+
+#[test]
+/// Removing modules passed by the bootloader doesn't free their strings.
+fn remove_bootloader_modules() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    let modules = [
+        Module::new(0x8000, 0x9000, Some("/boot/initrd")),
+        Module::new(0x9000, 0xa000, Some("/boot/font")),
+        Module::new(0xa000, 0xb000, Some("/boot/config")),
+    ];
+    MultibootInfoBuilder::new()
+        .modules(&modules)
+        .build(&mut mem.memory[0x1000..0x2000], 0x1000)
+        .unwrap();
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.remove_module(1);
+    multiboot.retain_modules(|module| module.has_name("initrd"));
+    assert_eq!(names(&multiboot), [Some("/boot/initrd")]);
+    multiboot.retain_modules(|_| false);
+    // only the copied strings and the edited tables
+    assert!(
+        mem.freed.iter().all(|&addr| addr >= 0x2000),
+        "{:x?}",
+        mem.freed
+    );
+    assert_eq!(mem.freed.len(), 5);
+}
+
+#[test]
+/// Only memory that was allocated through the same instance is freed.
+fn ownership() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    let memory_map = [MemoryEntry::new(0, 0x9fc00, MemoryType::Available)];
+    let modules = [Module::new(0x8000, 0x9000, Some("/boot/initrd"))];
+    // this is what the bootloader passed
+    MultibootInfoBuilder::new()
+        .command_line("/boot/kernel")
+        .modules(&modules)
+        .memory_map(&memory_map)
+        .build(&mut mem.memory[0x1000..0x2000], 0x1000)
+        .unwrap();
+    {
+        let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+        multiboot.set_command_line(Some("first"));
+        multiboot.push_module(Module::new(0x9000, 0xa000, Some("second")));
+        multiboot.remove_module(0);
+        multiboot.set_memory_map_and_bounds(Some(&memory_map));
+    }
+    // the copy of the string and the table allocated by push_module are freed,
+    // nothing the bootloader passed
+    assert_eq!(mem.freed.len(), 2);
+    assert!(
+        mem.freed.iter().all(|&addr| addr >= 0x2000),
+        "{:x?}",
+        mem.freed
+    );
+    mem.freed.clear();
+
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    multiboot.set_command_line(Some("first"));
+    let first = multiboot.command_line_bytes().unwrap().as_ptr() as usize;
+    multiboot.set_command_line(Some("second"));
+    multiboot.set_memory_map_and_bounds(Some(&memory_map));
+    multiboot.set_memory_map_and_bounds(None);
+    let freed = mem.freed.len();
+    assert_eq!(freed, 2);
+    assert_eq!(
+        mem.freed[0],
+        (first - mem.memory.as_ptr() as usize) as PAddr
+    );
+}
+
+#[test]
+/// Strings that were passed in and can't be read are kept, but never freed.
+fn unmapped_module_string() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    let modules = [
+        Module::new(0x8000, 0x9000, Some("/boot/initrd")),
+        Module::new(0x9000, 0xa000, Some("/boot/font")),
+    ];
+    // this is what the bootloader passed, the first string is past the end of memory
+    let pass_modules = |mem: &mut Mem| {
+        MultibootInfoBuilder::new()
+            .modules(&modules)
+            .build(&mut mem.memory[0x1000..0x2000], 0x1000)
+            .unwrap();
+        let table = {
+            let multiboot = unsafe { Multiboot::from_ptr(0x1000, mem).unwrap() };
+            module_table(&multiboot)
+        };
+        mem.write(table + 8, &0x20000u32.to_le_bytes());
+    };
+
+    pass_modules(&mut mem);
+    {
+        let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+        multiboot.remove_module(0);
+        assert_eq!(names(&multiboot), [Some("/boot/font")]);
+    }
+    assert!(mem.freed.is_empty(), "{:x?}", mem.freed);
+
+    pass_modules(&mut mem);
+    {
+        let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+        multiboot.push_module(Module::new(0xa000, 0xb000, Some("/boot/config")));
+        multiboot.retain_modules(|module| !module.has_name("font"));
+        assert_eq!(names(&multiboot), [None, Some("/boot/config")]);
+        multiboot.remove_module(0);
+        multiboot.set_modules(None);
+    }
+    // the copy of the second string, the third string and the edited tables
+    assert!(!mem.freed.contains(&0x20000), "{:x?}", mem.freed);
+    assert_eq!(mem.freed.len(), 5);
+}
+
+#[test]
+/// Everything that was allocated is freed, no matter how much it is.
+fn many_allocations() {
+    let mut mem = Mem::new(0x10000, 0x2000);
+    write_info(&mut mem, 0x1000);
+    let mut multiboot = unsafe { Multiboot::from_ptr(0x1000, &mut mem).unwrap() };
+    for index in 0..40 {
+        let name = format!("module {}", index);
+        multiboot.push_module(Module::new(0x8000, 0x9000, Some(&name)));
+    }
+    multiboot.set_modules(None);
+    // 40 strings and 40 tables
+    assert_eq!(mem.freed.len(), 80);
+}
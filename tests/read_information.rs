@@ -5,7 +5,7 @@ use core::convert::TryInto;
 use core::mem;
 use core::slice;
 use multiboot::information::{
-    ColorInfoType, MemoryManagement, MemoryType, Multiboot, PAddr, SymbolType,
+    ColorInfoType, MemoryAllocator, MemoryType, Multiboot, PAddr, PhysicalMemoryMapper, SymbolType,
 };
 
 const TEST_STR: [u8; 5] = [0x74, 0x65, 0x73, 0x74, 0x00]; // 'test'
@@ -24,7 +24,7 @@ const TEST_REGION: [u8; 24] = [
 
 struct Mem;
 
-impl MemoryManagement for Mem {
+impl PhysicalMemoryMapper for Mem {
     unsafe fn paddr_to_slice(&self, addr: PAddr, size: usize) -> Option<&'static [u8]> {
         match (addr, size) {
             (0xaaaaaaaa, sz) => Some(&TEST_STR[0..sz]),
@@ -41,7 +41,9 @@ impl MemoryManagement for Mem {
             }
         }
     }
+}
 
+impl MemoryAllocator for Mem {
     unsafe fn allocate(&mut self, _length: usize) -> Option<(PAddr, &mut [u8])> {
         None
     }
@@ -0,0 +1,165 @@
+extern crate core;
+extern crate multiboot;
+
+mod common;
+
+use common::Buffer;
+use multiboot::information::{
+    ColorInfoRgb, ColorInfoType, ElfSymbols, FramebufferTable, MemoryEntry, MemoryType, Module,
+    Multiboot, MultibootInfoBuilder, OccupiedRegionKind, PAddr, SymbolType, ValidationIssue,
+};
+
+const BASE: PAddr = 0x10000;
+
+/// Get a memory map with two available regions.
+fn memory_map() -> [MemoryEntry; 2] {
+    [
+        MemoryEntry::new(0, 0x9fc00, MemoryType::Available),
+        MemoryEntry::new(0x100000, 0x1f00000, MemoryType::Available),
+    ]
+}
+
+/// Get a 32 bit RGB framebuffer with the given pitch.
+fn framebuffer(pitch: u32) -> FramebufferTable {
+    let rgb = ColorInfoRgb {
+        red_field_position: 0,
+        red_mask_size: 8,
+        green_field_position: 8,
+        green_mask_size: 8,
+        blue_field_position: 16,
+        blue_mask_size: 8,
+    };
+    FramebufferTable::new(0xfd000000, pitch, 1024, 768, 32, ColorInfoType::Rgb(rgb))
+}
+
+#[test]
+/// Sane information has no issues.
+fn valid() {
+    let memory_map = memory_map();
+    let modules = [Module::new(
+        BASE + 0x1000,
+        BASE + 0x1100,
+        Some("/boot/initrd"),
+    )];
+    let mut mem = Buffer::build(
+        &MultibootInfoBuilder::new()
+            .memory_bounds((639, 31744))
+            .command_line("/boot/kernel quiet")
+            .boot_loader_name("validate")
+            .modules(&modules)
+            .elf_sections(&[0; 40 * 2], 40, 1)
+            .memory_map(&memory_map)
+            .framebuffer_table(framebuffer(4096)),
+        BASE,
+    );
+    mem.buffer.resize(0x2000, 0);
+    let multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+    let report = multiboot.validate();
+    assert!(report.is_valid(), "{:?}", report);
+    assert_eq!(report.issues().count(), 0);
+}
+
+#[test]
+/// Every kind of issue is reported in order.
+fn invalid() {
+    let modules = [
+        Module::new(BASE, BASE + 0x10, None),
+        Module::new(0x300, 0x200, Some("backwards")),
+        Module::new(0x200000, 0x201000, None),
+    ];
+    let long = "a".repeat(5000);
+    let memory_map = memory_map();
+    let mut mem = Buffer::build(
+        &MultibootInfoBuilder::new()
+            .memory_bounds((700, 31744))
+            .command_line("\u{e4}")
+            .boot_loader_name(&long)
+            .modules(&modules)
+            .elf_sections(&[0; 40 * 2], 40, 1)
+            .memory_map(&memory_map)
+            .framebuffer_table(framebuffer(1024)),
+        BASE,
+    );
+    // cut the command line in the middle of a character
+    mem.buffer[120 + 1] = 0;
+    let mut multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+    multiboot.set_has_aout_symbols(true);
+    multiboot.set_memory_regions(Some((0x800000, 2)));
+
+    let report = multiboot.validate();
+    assert!(!report.is_valid());
+    let issues: Vec<ValidationIssue> = report.issues().collect();
+    assert_eq!(
+        issues,
+        [
+            ValidationIssue::LowerMemoryTooLarge(700),
+            ValidationIssue::InvalidUtf8(OccupiedRegionKind::CommandLine),
+            ValidationIssue::UnterminatedString(OccupiedRegionKind::BootLoaderName),
+            ValidationIssue::ModuleOverlapsInfo(0),
+            ValidationIssue::InvalidModuleRange(1),
+            ValidationIssue::Unmapped(OccupiedRegionKind::Module(2)),
+            ValidationIssue::ConflictingSymbols,
+            ValidationIssue::Unmapped(OccupiedRegionKind::MemoryMap),
+            ValidationIssue::FramebufferPitchTooSmall,
+        ]
+    );
+    assert_eq!(report.missed(), 0);
+}
+
+#[test]
+/// Memory maps have to consist of whole entries of at least 20 bytes.
+fn memory_map_entries() {
+    let memory_map = memory_map();
+    let mut mem = Buffer::build(&MultibootInfoBuilder::new().memory_map(&memory_map), BASE);
+    // mmap_length
+    mem.buffer[44] = 2 * 24 - 4;
+    let issues: Vec<ValidationIssue> = {
+        let multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+        multiboot.validate().issues().collect()
+    };
+    assert_eq!(issues, [ValidationIssue::MemoryMapLength]);
+
+    mem.buffer[44] = 2 * 24;
+    // the size of the second entry, the map starts right after the information
+    mem.buffer[120 + 24] = 16;
+    let multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+    let issues: Vec<ValidationIssue> = multiboot.validate().issues().collect();
+    assert_eq!(issues, [ValidationIssue::MemoryEntryTooSmall(1)]);
+}
+
+#[test]
+/// Conflicting symbols are checked with both interpretations.
+fn conflicting_symbols() {
+    let mut mem = Buffer::build(&MultibootInfoBuilder::new(), BASE);
+    let end = BASE + mem.buffer.len() as PAddr;
+    let issues: Vec<ValidationIssue> = {
+        let mut multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+        // the ELF section headers take 16 bytes, the a.out tables 18
+        let symbols = ElfSymbols::from_addr(2, 8, end - 16, 0);
+        multiboot.set_symbols(Some(SymbolType::Elf(symbols)));
+        multiboot.set_has_aout_symbols(true);
+        multiboot.validate().issues().collect()
+    };
+    assert_eq!(
+        issues,
+        [
+            ValidationIssue::ConflictingSymbols,
+            ValidationIssue::Unmapped(OccupiedRegionKind::AOutSymbols),
+        ]
+    );
+}
+
+#[test]
+/// Only the first issues are stored.
+fn missed() {
+    let modules = [Module::new(0x300, 0x200, None); 20];
+    let mut mem = Buffer::build(&MultibootInfoBuilder::new().modules(&modules), BASE);
+    let multiboot = unsafe { Multiboot::from_ptr(BASE, &mut mem).unwrap() };
+    let report = multiboot.validate();
+    assert_eq!(report.issues().count(), 16);
+    assert_eq!(report.missed(), 4);
+    assert_eq!(
+        report.issues().last(),
+        Some(ValidationIssue::InvalidModuleRange(15))
+    );
+}
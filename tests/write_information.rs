@@ -3,8 +3,10 @@ extern crate multiboot;
 
 use core::mem;
 use multiboot::information::{
-    ColorInfoRgb, ColorInfoType, ElfSymbols, FramebufferTable, MemoryEntry, MemoryManagement,
-    MemoryType, Module, Multiboot, MultibootInfo, PAddr, SymbolType,
+    ColorInfoRgb, ColorInfoType, ElfSymbols, ExtendedMemoryEntry, FramebufferTable,
+    MemoryAllocator, MemoryEntry, MemoryType, Module, Multiboot, MultibootInfo, PAddr,
+    PhysicalMemoryMapper, SetError, SymbolType, EXTENDED_ATTRIBUTE_ENABLED,
+    EXTENDED_ATTRIBUTE_NON_VOLATILE,
 };
 
 struct Mem {
@@ -21,11 +23,13 @@ impl Mem {
     }
 }
 
-impl MemoryManagement for Mem {
+impl PhysicalMemoryMapper for Mem {
     unsafe fn paddr_to_slice(&self, _addr: PAddr, _size: usize) -> Option<&'static [u8]> {
         None
     }
+}
 
+impl MemoryAllocator for Mem {
     unsafe fn allocate(&mut self, length: usize) -> Option<(PAddr, &mut [u8])> {
         match length {
             5 => Some((0x12345678, &mut self.string_buffer)), // for our test string
@@ -342,6 +346,34 @@ fn mmap() {
     );
 }
 
+#[test]
+/// Create memory entries with extended attributes and unknown types.
+fn extended_mmap() {
+    let memory_entry = MemoryEntry::with_extended_attributes(
+        0x12345678,
+        4096,
+        MemoryType::Persistent,
+        EXTENDED_ATTRIBUTE_ENABLED | EXTENDED_ATTRIBUTE_NON_VOLATILE,
+    );
+    let expected_entry: [u8; 28] = [
+        0x18, 0x00, 0x00, 0x00, // size
+        0x78, 0x56, 0x34, 0x12, 0x00, 0x00, 0x00, 0x00, // base_addr
+        0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // length
+        0x07, 0x00, 0x00, 0x00, // type
+        0x03, 0x00, 0x00, 0x00, // extended attributes
+    ];
+    assert_eq!(
+        unsafe { mem::transmute::<ExtendedMemoryEntry, [u8; 28]>(memory_entry) },
+        expected_entry
+    );
+    let memory_entry = MemoryEntry::new(0x0, 0x1000, MemoryType::Other(0xf00d));
+    assert_eq!(memory_entry.memory_type(), MemoryType::Other(0xf00d));
+    assert_eq!(
+        unsafe { mem::transmute::<MemoryEntry, [u8; 24]>(memory_entry) }[20..],
+        [0x0d, 0xf0, 0x00, 0x00]
+    );
+}
+
 #[test]
 /// Also set the boot loader name.
 fn boot_loader_name() {
@@ -447,6 +479,55 @@ fn framebuffer() {
     assert_eq!(multiboot_info_to_bytes(info), expected);
 }
 
+#[test]
+/// Failing setters leave the information alone.
+fn fallible() {
+    let mut mem = Mem::new();
+    let mut info = MultibootInfo::default();
+    let mut multiboot = Multiboot::from_ref(&mut info, &mut mem);
+    assert_eq!(
+        multiboot.try_set_command_line(Some("too long")),
+        Err(SetError::AllocationFailed)
+    );
+    assert!(!multiboot.has_cmdline());
+    assert_eq!(
+        multiboot.try_set_command_line(Some("a\0b")),
+        Err(SetError::InteriorNul)
+    );
+    assert_eq!(multiboot.try_set_command_line(Some("test")), Ok(()));
+    // nothing is freed (which would panic) and the flag stays
+    assert_eq!(
+        multiboot.try_set_boot_loader_name(Some("too long")),
+        Err(SetError::AllocationFailed)
+    );
+    assert!(multiboot.has_cmdline());
+
+    assert_eq!(
+        multiboot.try_set_modules(Some(&[Module::new(0x1_0000_0000, 0x1_0000_1000, None)])),
+        Err(SetError::AddressTooLarge)
+    );
+    assert_eq!(
+        multiboot.try_set_modules(Some(&[Module::new(0, 1, None), Module::new(1, 2, None)])),
+        Err(SetError::AllocationFailed)
+    );
+    assert!(!multiboot.has_modules());
+
+    assert_eq!(
+        multiboot.try_set_memory_regions(Some((0x1_0000_0000, 1))),
+        Err(SetError::AddressTooLarge)
+    );
+    assert_eq!(
+        multiboot.try_set_memory_regions(Some((0x1000, usize::MAX / 2))),
+        Err(SetError::TooManyEntries)
+    );
+    assert!(!multiboot.has_memory_map());
+
+    assert_eq!(
+        ElfSymbols::try_from_addr(1, 40, 0x1_0000_0000, 0).map(|_| ()),
+        Err(SetError::AddressTooLarge)
+    );
+}
+
 fn multiboot_info_to_bytes(info: MultibootInfo) -> [u8; 120] {
     assert_eq!(mem::size_of::<MultibootInfo>(), 120);
 